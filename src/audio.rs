@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy_kira_audio::prelude::*;
+
+use crate::{
+    game::{
+        combat::{DamageEvent, DeathEvent, FireEvent},
+        factions::Faction,
+    },
+    settings::Settings,
+};
+
+pub mod music;
+
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(music::MusicPlugin)
+            .add_audio_channel::<MusicChannel>()
+            .add_audio_channel::<SfxChannel>()
+            .add_audio_channel::<UiChannel>()
+            .add_event::<SoundEvent>()
+            .init_resource::<SoundAssets>()
+            .init_resource::<VoiceLimiter>()
+            .add_systems(Update, (
+                apply_volume_settings.run_if(resource_changed::<Settings>()),
+                (
+                    combat_sounds,
+                    play_sounds,
+                ).chain(),
+            ));
+    }
+}
+
+#[derive(Resource)]
+pub struct MusicChannel;
+
+#[derive(Resource)]
+pub struct SfxChannel;
+
+#[derive(Resource)]
+pub struct UiChannel;
+
+/// Send this to play a sound effect. Identical sounds sent in the same frame only play once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Event)]
+pub enum SoundEvent {
+    PlayerFire,
+    EnemyFire,
+    Hit,
+    PlayerHit,
+    Death,
+    Pickup,
+    UiClick,
+}
+
+impl SoundEvent {
+    const ALL: [Self; 7] = [
+        Self::PlayerFire,
+        Self::EnemyFire,
+        Self::Hit,
+        Self::PlayerHit,
+        Self::Death,
+        Self::Pickup,
+        Self::UiClick,
+    ];
+
+    pub fn fire(faction: Faction) -> Self {
+        match faction {
+            Faction::Player => Self::PlayerFire,
+            Faction::Enemy => Self::EnemyFire,
+        }
+    }
+
+    fn path(&self) -> &'static str {
+        match self {
+            Self::PlayerFire => "sounds/player_fire.wav",
+            Self::EnemyFire => "sounds/enemy_fire.wav",
+            Self::Hit => "sounds/hit.wav",
+            Self::PlayerHit => "sounds/player_hit.wav",
+            Self::Death => "sounds/death.wav",
+            Self::Pickup => "sounds/pickup.wav",
+            Self::UiClick => "sounds/ui_click.wav",
+        }
+    }
+
+    /// How many copies of this sound can play at once.
+    fn max_voices(&self) -> usize {
+        match self {
+            Self::PlayerFire | Self::EnemyFire => 4,
+            Self::Hit | Self::Death => 6,
+            Self::PlayerHit | Self::Pickup | Self::UiClick => 2,
+        }
+    }
+
+    /// The shortest time in seconds between two copies of this sound starting.
+    fn min_interval(&self) -> f32 {
+        match self {
+            Self::PlayerFire | Self::EnemyFire => 0.05,
+            Self::Hit | Self::Death => 0.03,
+            Self::PlayerHit | Self::Pickup | Self::UiClick => 0.0,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct SoundAssets {
+    sounds: HashMap<SoundEvent, Handle<AudioSource>>,
+}
+
+impl FromWorld for SoundAssets {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Self {
+            sounds: SoundEvent::ALL.iter()
+                .map(|&sound| (sound, asset_server.load(sound.path())))
+                .collect(),
+        }
+    }
+}
+
+/// Keeps track of playing sounds so a big swarm firing at once doesn't stack hundreds of copies
+/// of the same sound.
+#[derive(Default, Resource)]
+struct VoiceLimiter {
+    voices: HashMap<SoundEvent, Vec<Handle<AudioInstance>>>,
+    last_played: HashMap<SoundEvent, f32>,
+}
+
+// Music volume is handled by the music controller, since it mixes stems by setting their volumes.
+fn apply_volume_settings(
+    settings: Res<Settings>,
+    sfx: Res<AudioChannel<SfxChannel>>,
+    ui: Res<AudioChannel<UiChannel>>,
+) {
+    let audio = &settings.audio;
+    sfx.set_volume((audio.master * audio.sfx) as f64);
+    ui.set_volume((audio.master * audio.ui) as f64);
+}
+
+fn combat_sounds(
+    mut fire_events: EventReader<FireEvent>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventReader<DeathEvent>,
+    mut sound_events: EventWriter<SoundEvent>,
+) {
+    for event in fire_events.read() {
+        sound_events.send(SoundEvent::fire(event.faction));
+    }
+    for event in damage_events.read() {
+        sound_events.send(match event.faction {
+            Faction::Player => SoundEvent::PlayerHit,
+            Faction::Enemy => SoundEvent::Hit,
+        });
+    }
+    for _ in death_events.read() {
+        sound_events.send(SoundEvent::Death);
+    }
+}
+
+fn play_sounds(
+    time: Res<Time>,
+    sounds: Res<SoundAssets>,
+    mut limiter: ResMut<VoiceLimiter>,
+    mut sound_events: EventReader<SoundEvent>,
+    sfx: Res<AudioChannel<SfxChannel>>,
+    ui: Res<AudioChannel<UiChannel>>,
+) {
+    let requested: HashSet<SoundEvent> = sound_events.read().copied().collect();
+    let now = time.elapsed_seconds();
+    let limiter = &mut *limiter;
+
+    for sound in requested {
+        if let Some(&last_played) = limiter.last_played.get(&sound) {
+            if now - last_played < sound.min_interval() {
+                continue;
+            }
+        }
+
+        let voices = limiter.voices.entry(sound).or_default();
+        let played = match sound {
+            SoundEvent::UiClick => play_limited(&ui, &sounds.sounds[&sound], voices, sound.max_voices()),
+            _ => play_limited(&sfx, &sounds.sounds[&sound], voices, sound.max_voices()),
+        };
+        if played {
+            limiter.last_played.insert(sound, now);
+        }
+    }
+}
+
+/// Plays a sound unless too many copies of it are already playing. Returns whether it played.
+fn play_limited<T: Resource>(
+    channel: &AudioChannel<T>,
+    source: &Handle<AudioSource>,
+    voices: &mut Vec<Handle<AudioInstance>>,
+    max_voices: usize,
+) -> bool {
+    voices.retain(|voice| !matches!(channel.state(voice), PlaybackState::Stopped));
+    if voices.len() >= max_voices {
+        return false;
+    }
+
+    voices.push(channel.play(source.clone()).handle());
+    true
+}
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::*;
+
+use crate::{
+    audio::MusicChannel,
+    game::waves::WavesManager,
+    settings::{Settings, SettingsMenu},
+};
+
+// All stems share this tempo and are the same length, so they stay in sync while looping.
+const BPM: f64 = 120.0;
+const BEATS_PER_BAR: f64 = 4.0;
+// How long state changes take to crossfade, in bars.
+const CROSSFADE_BARS: f64 = 1.0;
+// How long volume tweaks take, for example when changing the music volume setting.
+const ADJUST_FADE: Duration = Duration::from_millis(250);
+// How many waves it takes for combat music to reach full intensity.
+const FULL_INTENSITY_WAVE: f32 = 4.0;
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MusicController>()
+            .add_systems(Update, (
+                start_music,
+                update_music,
+            ).chain());
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MusicState {
+    #[default]
+    Menu,
+    Calm,
+    Combat,
+    Boss,
+}
+
+impl MusicState {
+    /// How loud a stem should be in this state. Intensity goes from 0.0 to 1.0.
+    fn stem_volume(&self, stem: Stem, intensity: f32) -> f32 {
+        match (self, stem) {
+            (Self::Menu, Stem::Pad) => 0.8,
+            (Self::Menu, _) => 0.0,
+            (Self::Calm, Stem::Pad) => 1.0,
+            (Self::Calm, Stem::Drums) => 0.3,
+            (Self::Calm, _) => 0.0,
+            (Self::Combat, Stem::Pad) => 1.0 - 0.5 * intensity,
+            (Self::Combat, Stem::Drums) => 1.0,
+            (Self::Combat, Stem::Lead) => intensity,
+            (Self::Combat, Stem::Bass) => 0.0,
+            (Self::Boss, Stem::Pad) => 0.0,
+            (Self::Boss, _) => 1.0,
+        }
+    }
+}
+
+/// One layer of the music. Every stem loops the whole time and states mix them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stem {
+    Pad,
+    Drums,
+    Lead,
+    Bass,
+}
+
+impl Stem {
+    const ALL: [Self; 4] = [Self::Pad, Self::Drums, Self::Lead, Self::Bass];
+
+    fn path(&self) -> &'static str {
+        match self {
+            Self::Pad => "music/calm_pad.wav",
+            Self::Drums => "music/combat_drums.wav",
+            Self::Lead => "music/combat_lead.wav",
+            Self::Bass => "music/boss_bass.wav",
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct MusicController {
+    sources: Vec<Handle<AudioSource>>,
+    // Playing stems, in the same order as Stem::ALL. Empty until the stems finish loading.
+    instances: Vec<Handle<AudioInstance>>,
+    state: MusicState,
+    // State to switch to at the start of the next bar.
+    pending: Option<MusicState>,
+    last_position: f64,
+    volumes: [f32; Stem::ALL.len()],
+}
+
+impl FromWorld for MusicController {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Self {
+            sources: Stem::ALL.iter().map(|stem| asset_server.load(stem.path())).collect(),
+            instances: Vec::new(),
+            state: default(),
+            pending: None,
+            last_position: 0.0,
+            volumes: default(),
+        }
+    }
+}
+
+fn bar_length() -> f64 {
+    60.0 / BPM * BEATS_PER_BAR
+}
+
+fn start_music(
+    audio_sources: Res<Assets<AudioSource>>,
+    channel: Res<AudioChannel<MusicChannel>>,
+    mut controller: ResMut<MusicController>,
+) {
+    if !controller.instances.is_empty() {
+        return;
+    }
+    // Wait for every stem so they all start on the same frame.
+    if !controller.sources.iter().all(|source| audio_sources.contains(source)) {
+        return;
+    }
+
+    controller.instances = controller.sources.iter()
+        .map(|source| channel.play(source.clone()).looped().with_volume(0.0).handle())
+        .collect();
+}
+
+fn desired_music_state(
+    settings_menu: &SettingsMenu,
+    waves: &WavesManager,
+) -> MusicState {
+    if settings_menu.open {
+        MusicState::Menu
+    } else if waves.in_break() {
+        MusicState::Calm
+    } else if waves.is_boss_wave() {
+        MusicState::Boss
+    } else {
+        MusicState::Combat
+    }
+}
+
+fn update_music(
+    settings: Res<Settings>,
+    settings_menu: Res<SettingsMenu>,
+    waves: Res<WavesManager>,
+    channel: Res<AudioChannel<MusicChannel>>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+    mut controller: ResMut<MusicController>,
+) {
+    let controller = &mut *controller;
+    let Some(first) = controller.instances.first() else {
+        return;
+    };
+
+    let desired = desired_music_state(&settings_menu, &waves);
+    controller.pending = (desired != controller.state).then_some(desired);
+
+    // Only switch states on a bar line so transitions land on the beat.
+    let mut fade = ADJUST_FADE;
+    if let PlaybackState::Playing { position } = channel.state(first) {
+        let bar = bar_length();
+        let crossed_bar = (position / bar).floor() != (controller.last_position / bar).floor();
+        // Looping back to the start also counts as crossing a bar line.
+        if crossed_bar || position < controller.last_position {
+            if let Some(pending) = controller.pending.take() {
+                controller.state = pending;
+                fade = Duration::from_secs_f64(bar * CROSSFADE_BARS);
+            }
+        }
+        controller.last_position = position;
+    }
+
+    // Builds up as each wave is cleared, not just when the next one arrives.
+    let waves_cleared = match waves.wave() {
+        0 => 0.0,
+        wave => (wave - 1) as f32 + waves.progress(),
+    };
+    let intensity = (waves_cleared / FULL_INTENSITY_WAVE).min(1.0);
+    let music_volume = settings.audio.master * settings.audio.music;
+    for (i, stem) in Stem::ALL.iter().enumerate() {
+        let volume = controller.state.stem_volume(*stem, intensity) * music_volume;
+        if (volume - controller.volumes[i]).abs() < 0.001 {
+            continue;
+        }
+        if let Some(instance) = audio_instances.get_mut(&controller.instances[i]) {
+            instance.set_volume(volume as f64, AudioTween::linear(fade));
+            controller.volumes[i] = volume;
+        }
+    }
+}
@@ -1,6 +1,10 @@
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+    window::PrimaryWindow,
+};
 use bevy_egui::{
-    egui::{self, Align2, Color32, ComboBox, DragValue, Frame, RichText},
+    egui::{self, Align2, ComboBox, DragValue},
     EguiContexts,
 };
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
@@ -10,7 +14,10 @@ use strum::{EnumCount, EnumVariantNames, VariantNames};
 
 use crate::{
     // enemies::spawner::Spawner,
-    game::{ai, enemies, input, units::{self, SwarmParent}},
+    persist,
+    settings::SettingsMenu,
+    GAME_SIZE,
+    game::{ai, camera::{self, CameraRig}, enemies, input, particles::ParticlePool, patterns, projectiles::ProjectilePool, run::Run, scenario, units::{self, SwarmParent}, waves::WavesManager},
 };
 
 pub struct DebugPlugin;
@@ -28,11 +35,16 @@ impl Plugin for DebugPlugin {
             // otherwise.
             .add_systems(Update, (
                 debug_menu_bar.run_if(debug_ui_enabled),
-                debug_ui,
+                debug_ui.run_if(debug_ui_enabled),
                 toggle_debug_ui,
                 toggle_physics_debug_render,
+                toggle_free_camera,
                 place_entity.run_if(place_entity_mode_enabled),
             ).before(input::read_player_input))
+            .add_systems(Update, (
+                sync_free_camera,
+                free_camera_control.run_if(free_camera_enabled),
+            ).chain())
             .add_systems(Last, update_mouse_cursor);
     }
 }
@@ -43,14 +55,31 @@ enum PlaceEntityMode {
     #[default]
     None,
     Enemy,
+    PatternEnemy,
 }
 
+const PATTERN_FILES: &[&str] = &[
+    "patterns/ring.pattern.ron",
+    "patterns/spiral.pattern.ron",
+    "patterns/fan.pattern.ron",
+    "patterns/aimed_burst.pattern.ron",
+    "patterns/stream.pattern.ron",
+    "patterns/homing_fan.pattern.ron",
+    "patterns/splitter.pattern.ron",
+];
+
 #[derive(Resource)]
 struct DebugState {
     enabled: bool,
     show_world_inspector: bool,
     resize_swarm_count: u32,
     place_entity_mode: PlaceEntityMode,
+    place_pattern: usize,
+    /// Placed pattern enemies fire straight down instead of at the nearest opponent.
+    place_pattern_fixed_aim: bool,
+    free_camera: bool,
+    /// Scene file to dump to and load from, in the config directory.
+    scenario_file: String,
 }
 
 impl Default for DebugState {
@@ -60,6 +89,10 @@ impl Default for DebugState {
             show_world_inspector: false,
             resize_swarm_count: 10,
             place_entity_mode: default(),
+            place_pattern: 0,
+            place_pattern_fixed_aim: false,
+            free_camera: false,
+            scenario_file: "scenario.scn.ron".to_string(),
         }
     }
 }
@@ -82,10 +115,17 @@ fn place_entity_mode_enabled(
     debug_ui.enabled && debug_ui.place_entity_mode != PlaceEntityMode::None
 }
 
+fn free_camera_enabled(
+    debug_ui: Res<DebugState>,
+) -> bool {
+    debug_ui.enabled && debug_ui.free_camera
+}
+
 fn debug_menu_bar(
     mut debug_state: ResMut<DebugState>,
     mut debug_physics_ctx: ResMut<DebugRenderContext>,
     mut egui_ctx: EguiContexts,
+    camera_q: Query<(&Transform, &OrthographicProjection, &CameraRig)>,
 ) {
     let ctx = egui_ctx.ctx_mut();
 
@@ -98,8 +138,17 @@ fn debug_menu_bar(
                 ui.menu_button("Debug", |ui| {
                     ui.checkbox(&mut debug_state.show_world_inspector, "World Inspector");
                     ui.checkbox(&mut debug_physics_ctx.enabled, "Debug Physics Render");
+                    ui.checkbox(&mut debug_state.free_camera, "Free Camera");
                     // ui.checkbox(&mut debug_state.place_entity_mode, "Place Entity Mode");
                 });
+
+                if let Ok((transform, projection, rig)) = camera_q.get_single() {
+                    let pos = transform.translation;
+                    let focus = rig.focus();
+                    ui.label(format!("Camera: ({:.0}, {:.0}) x{:.2}", pos.x, pos.y, projection.scale));
+                    ui.label(format!("Focus: ({:.0}, {:.0})", focus.x, focus.y));
+                    ui.label(format!("Trauma: {:.2}", rig.trauma()));
+                }
             });
         });
 }
@@ -108,59 +157,93 @@ fn debug_ui(
     mut commands: Commands,
     mut debug_state: ResMut<DebugState>,
     mut egui_ctx: EguiContexts,
-    swarm_q: Query<(Entity, &Children), With<SwarmParent>>,
+    projectile_pool: Res<ProjectilePool>,
+    particle_pool: Res<ParticlePool>,
+    swarm_q: Query<(Entity, &Children, &SwarmParent)>,
 ) {
     let ctx = egui_ctx.ctx_mut();
     let swarm_size = swarm_q.get_single()
-        .map(|(_, children)| children.len())
+        .map(|(_, children, _)| children.len())
         .unwrap_or_default();
-    let swarm_text = RichText::new(format!("Swarm Size: {}", swarm_size))
-        .color(Color32::WHITE)
-        .size(20.0);
-
-    if debug_state.enabled {
-        egui::Window::new("temp_side_panel")
-            .anchor(Align2::RIGHT_TOP, (-10.0, 100.0))
-            .title_bar(false)
-            .collapsible(false)
-            .auto_sized()
-            .show(ctx, |ui| {
-                ui.label(swarm_text);
 
-                ui.horizontal(|ui| {
-                    if ui.button("Resize Swarm").clicked() {
-                        units::resize_swarm(&mut commands, &swarm_q, debug_state.resize_swarm_count)
-                    }
+    egui::Window::new("Debug Tools")
+        .anchor(Align2::RIGHT_TOP, (-10.0, 100.0))
+        .collapsible(false)
+        .auto_sized()
+        .show(ctx, |ui| {
+            ui.label(format!("Swarm Size: {}", swarm_size));
 
-                    ui.add(DragValue::new(&mut debug_state.resize_swarm_count));
-                });
+            ui.horizontal(|ui| {
+                if ui.button("Resize Swarm").clicked() {
+                    units::resize_swarm(&mut commands, &swarm_q, debug_state.resize_swarm_count)
+                }
 
-                let selected: u8 = debug_state.place_entity_mode.into();
-                let mut selected = selected as usize;
-                ComboBox::from_label("Place Entity")
-                    .show_index(ui, &mut selected, PlaceEntityMode::COUNT, |i| PlaceEntityMode::VARIANTS[i]);
-                debug_state.place_entity_mode = PlaceEntityMode::from(selected as u8);
+                ui.add(DragValue::new(&mut debug_state.resize_swarm_count));
             });
-    } else {
-        egui::Window::new("temp_side_panel")
-            .anchor(Align2::RIGHT_TOP, (-10.0, 100.0))
-            .title_bar(false)
-            .collapsible(false)
-            .frame(Frame::none())
-            .auto_sized()
-            .show(ctx, |ui| {
-                ui.label(swarm_text);
+
+            ui.collapsing("Projectile Pool", |ui| {
+                ui.label(format!("Active: {}", projectile_pool.active()));
+                ui.label(format!("Pooled: {}", projectile_pool.pooled()));
+                ui.label(format!("Spawned: {}", projectile_pool.spawned()));
+                ui.label(format!("Reused: {}", projectile_pool.reused()));
             });
-    }
+
+            ui.collapsing("Particle Pool", |ui| {
+                ui.label(format!("Active: {}", particle_pool.active()));
+                ui.label(format!("Pooled: {}", particle_pool.pooled()));
+            });
+
+            ui.collapsing("Scenarios", |ui| {
+                ui.text_edit_singleline(&mut debug_state.scenario_file);
+                let path = persist::config_path(&debug_state.scenario_file);
+                ui.horizontal(|ui| {
+                    if ui.button("Dump").clicked() {
+                        let path = path.clone();
+                        commands.add(move |world: &mut World| {
+                            match scenario::save_scenario(world, &path) {
+                                Ok(()) => info!("Dumped scenario to {}", path.display()),
+                                Err(e) => error!("Could not dump scenario: {}", e),
+                            }
+                        });
+                    }
+                    if ui.button("Load").clicked() {
+                        let path = path.clone();
+                        commands.add(move |world: &mut World| {
+                            if let Err(e) = scenario::load_scenario(world, &path) {
+                                error!("Could not load scenario from {}: {}", path.display(), e);
+                            }
+                        });
+                    }
+                });
+            });
+
+            let selected: u8 = debug_state.place_entity_mode.into();
+            let mut selected = selected as usize;
+            ComboBox::from_label("Place Entity")
+                .show_index(ui, &mut selected, PlaceEntityMode::COUNT, |i| PlaceEntityMode::VARIANTS[i]);
+            debug_state.place_entity_mode = PlaceEntityMode::from(selected as u8);
+
+            if debug_state.place_entity_mode == PlaceEntityMode::PatternEnemy {
+                ComboBox::from_label("Pattern")
+                    .show_index(ui, &mut debug_state.place_pattern, PATTERN_FILES.len(), |i| PATTERN_FILES[i]);
+                ui.checkbox(&mut debug_state.place_pattern_fixed_aim, "Fire straight down");
+            }
+        });
 }
 
 fn update_mouse_cursor(
     debug_state: Res<DebugState>,
+    settings_menu: Res<SettingsMenu>,
+    run: Res<Run>,
+    waves: Res<WavesManager>,
     mut window_q: Query<&mut Window, With<PrimaryWindow>>,
 ) {
     if let Ok(mut window) = window_q.get_single_mut() {
         // TODO: Make UI egui windows non-interactable and remove the debug_state.enabled check.
-        let show_cursor = debug_state.enabled; //&& egui_ctx.ctx_mut().wants_pointer_input();
+        let show_cursor = debug_state.enabled
+            || settings_menu.open
+            || run.is_over()
+            || waves.in_intermission();
         window.cursor.visible = show_cursor;
     }
 }
@@ -195,19 +278,122 @@ fn toggle_physics_debug_render(
 
 fn place_entity(
     mut commands: Commands,
+    debug_state: Res<DebugState>,
+    asset_server: Res<AssetServer>,
     mouse_buttons: Res<Input<MouseButton>>,
     mut egui_ctx: EguiContexts,
-    camera_q: Query<(&Camera, &GlobalTransform)>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<CameraRig>>,
     primary_window_q: Query<&Window, With<PrimaryWindow>>,
 ) {
     if egui_ctx.ctx_mut().wants_pointer_input() || !mouse_buttons.just_pressed(MouseButton::Left) {
         return;
     }
 
-    if let Some(pos) = input::get_mouse_world_pos(&primary_window_q, &camera_q) {
-        commands.spawn((
-            enemies::EnemyBundle::new(pos),
-            ai::SimpleShooterAi::new(3.0, 0.0..=2.0),
-        ));
+    let Some(pos) = input::get_mouse_world_pos(&primary_window_q, &camera_q) else {
+        return;
+    };
+    match debug_state.place_entity_mode {
+        PlaceEntityMode::None => {},
+        PlaceEntityMode::Enemy => {
+            commands.spawn((
+                enemies::EnemyBundle::new(pos),
+                ai::SimpleShooterAi::new(3.0, 0.0..=2.0),
+            ));
+        },
+        PlaceEntityMode::PatternEnemy => {
+            let pattern = asset_server.load(PATTERN_FILES[debug_state.place_pattern]);
+            let aim = if debug_state.place_pattern_fixed_aim {
+                patterns::EmitterAim::Fixed(Vec2::NEG_Y)
+            } else {
+                patterns::EmitterAim::NearestOpponent
+            };
+            commands.spawn((
+                enemies::EnemyBundle::new(pos),
+                patterns::PatternEmitterBundle::from_asset(pattern, aim),
+            ));
+        },
+    }
+}
+
+fn toggle_free_camera(
+    keys: ResMut<Input<KeyCode>>,
+    mut egui_ctx: EguiContexts,
+    mut debug_state: ResMut<DebugState>,
+) {
+    if egui_ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    if debug_state.enabled && keys.just_pressed(KeyCode::Key9) {
+        debug_state.free_camera = !debug_state.free_camera;
+    }
+}
+
+fn sync_free_camera(
+    debug_state: Res<DebugState>,
+    mut camera_q: Query<(&mut CameraRig, &mut OrthographicProjection)>,
+) {
+    let Ok((mut rig, mut projection)) = camera_q.get_single_mut() else {
+        return;
+    };
+
+    let rig_enabled = !(debug_state.enabled && debug_state.free_camera);
+    if rig.enabled != rig_enabled {
+        rig.enabled = rig_enabled;
+        if rig_enabled {
+            // Snap back to the gameplay camera. The rig will put the transform back itself.
+            projection.scale = rig.zoom;
+        }
+    }
+}
+
+fn free_camera_control(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut egui_ctx: EguiContexts,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection, &Camera), With<CameraRig>>,
+) {
+    let Ok((mut transform, mut projection, game_camera)) = camera_q.get_single_mut() else {
+        return;
+    };
+    let ctx = egui_ctx.ctx_mut();
+
+    // Pan with the arrow keys. Hold shift to go faster.
+    if !ctx.wants_keyboard_input() {
+        let x = (keys.pressed(KeyCode::Right) as i8 - keys.pressed(KeyCode::Left) as i8) as f32;
+        let y = (keys.pressed(KeyCode::Up) as i8 - keys.pressed(KeyCode::Down) as i8) as f32;
+        let fast = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let speed = if fast { 2000.0 } else { 600.0 };
+        let movement = Vec2::new(x, y).normalize_or_zero() * speed * projection.scale * time.delta_seconds();
+        transform.translation += movement.extend(0.0);
+    }
+
+    if ctx.wants_pointer_input() {
+        mouse_motion.clear();
+        wheel_events.clear();
+        return;
+    }
+
+    // Drag with the right mouse button to pan.
+    let drag: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+    if mouse_buttons.pressed(MouseButton::Right) {
+        // The game is drawn at a fixed resolution and scaled up to fit the viewport, so convert
+        // screen pixels to world units to keep the world under the cursor.
+        let viewport_width = game_camera.logical_viewport_size().map_or(GAME_SIZE.0, |size| size.x);
+        let world_per_pixel = projection.scale * GAME_SIZE.0 / viewport_width;
+        transform.translation += Vec2::new(-drag.x, drag.y).extend(0.0) * world_per_pixel;
+    }
+
+    // Zoom with the mouse wheel.
+    for event in wheel_events.read() {
+        let lines = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / camera::PIXELS_PER_LINE,
+        };
+        projection.scale = (projection.scale * camera::ZOOM_STEP.powf(-lines)).clamp(0.1, 20.0);
     }
+    transform.rotation = Quat::IDENTITY;
 }
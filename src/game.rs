@@ -1,44 +1,112 @@
 use bevy::prelude::*;
 
+use crate::settings::Settings;
 use units::SwarmParent;
 
 pub mod ai;
+pub mod arena;
 pub mod camera;
 pub mod combat;
 pub mod enemies;
 pub mod factions;
+pub mod feedback;
+pub mod headless;
 pub mod health;
+pub mod hud;
 pub mod input;
+pub mod meta;
+pub mod particles;
+pub mod patterns;
 pub mod projectiles;
+pub mod run;
+pub mod save;
+pub mod scenario;
+pub mod score;
+pub mod sprites;
 pub mod units;
+pub mod upgrades;
 pub mod waves;
 
-pub struct GamePlugin;
+/// The whole game. Use GamePlugin::headless() to leave out the camera, visual effects, UI and
+/// save files, e.g. for tests.
+#[derive(Default)]
+pub struct GamePlugin {
+    headless: bool,
+}
+
+impl GamePlugin {
+    pub fn headless() -> Self {
+        Self {
+            headless: true,
+        }
+    }
+}
+
+/// Inserted when the game is running without rendering, audio or UI.
+#[derive(Resource)]
+pub struct Headless;
+
+/// Whether the app was set up with GamePlugin::headless(). Plugins use this to skip UI systems.
+pub fn is_headless(app: &App) -> bool {
+    app.world.contains_resource::<Headless>()
+}
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
+        if self.headless {
+            app
+                .insert_resource(Headless)
+                // These are normally loaded from disk by SettingsPlugin and MetaPlugin.
+                .init_resource::<Settings>()
+                .init_resource::<meta::MetaProgress>();
+        }
+
+        // Gameplay.
         app
             .add_plugins((
                 ai::AiPlugin,
-                camera::CameraPlugin,
+                arena::ArenaPlugin,
                 combat::CombatPlugin,
                 enemies::EnemiesPlugin,
                 health::HealthPlugin,
-                input::InputPlugin,
+                patterns::PatternsPlugin,
                 projectiles::ProjectilesPlugin,
+                run::RunPlugin,
+                score::ScorePlugin,
                 units::UnitsPlugin,
+                upgrades::UpgradesPlugin,
                 waves::WavesPlugin,
             ))
             .add_systems(Startup, start_game);
+
+        // Presentation, input and persistence.
+        if !self.headless {
+            app.add_plugins((
+                camera::CameraPlugin,
+                feedback::FeedbackPlugin,
+                hud::HudPlugin,
+                input::InputPlugin,
+                meta::MetaPlugin,
+                particles::ParticlesPlugin,
+                save::SavePlugin,
+                sprites::SpritesPlugin,
+            ));
+        }
     }
 }
 
 fn start_game(
     mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    meta: Res<meta::MetaProgress>,
+    settings: Res<Settings>,
+    headless: Option<Res<Headless>>,
     swarm_q: Query<Entity, With<SwarmParent>>,
 ) {
-    camera::spawn_camera(&mut commands, 2.0);
+    if headless.is_none() {
+        camera::spawn_camera(&mut commands, 5.0, &settings);
+    }
 
     // Spawn swarm
-    units::spawn_swarm(&mut commands, &swarm_q, 20);
+    run::start_run(&mut commands, &swarm_q, &meta, &asset_server);
 }
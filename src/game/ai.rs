@@ -3,8 +3,9 @@ use std::ops::RangeInclusive;
 use bevy::prelude::*;
 
 use crate::game::{
+    combat::FireEvent,
     factions::Faction,
-    projectiles::ProjectileBundle,
+    projectiles::Projectiles,
 };
 
 pub struct AiPlugin;
@@ -12,34 +13,46 @@ pub struct AiPlugin;
 impl Plugin for AiPlugin {
     fn build(&self, app: &mut App) {
         app
+            .register_type::<SimpleShooterAi>()
             .add_systems(Update, simple_shooter_ai);
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 pub struct SimpleShooterAi {
     cooldown: f32,
-    variance: RangeInclusive<f32>,
+    // Stored as two values instead of a range so it can be reflected and saved in scenes.
+    min_variance: f32,
+    max_variance: f32,
     cooldown_expires: f32,
 }
 
+impl Default for SimpleShooterAi {
+    fn default() -> Self {
+        Self::new(3.0, 0.0..=2.0)
+    }
+}
+
 impl SimpleShooterAi {
     pub fn new(cooldown: f32, variance: RangeInclusive<f32>) -> Self {
         Self {
             cooldown,
-            variance,
+            min_variance: *variance.start(),
+            max_variance: *variance.end(),
             cooldown_expires: -1.0,
         }
     }
 }
 
 fn simple_shooter_ai(
-    mut commands: Commands,
+    mut projectiles: Projectiles,
     time: Res<Time>,
-    mut ai_q: Query<(&mut SimpleShooterAi, &GlobalTransform, &Faction)>,
+    mut fire_events: EventWriter<FireEvent>,
+    mut ai_q: Query<(Entity, &mut SimpleShooterAi, &GlobalTransform, &Faction)>,
 ) {
     let now = time.elapsed_seconds();
-    for (mut ai, transform, faction) in ai_q.iter_mut() {
+    for (entity, mut ai, transform, faction) in ai_q.iter_mut() {
         if ai.cooldown_expires > now {
             continue;
         }
@@ -48,12 +61,18 @@ fn simple_shooter_ai(
             // Spawn a projectile.
             let pos = transform.translation().truncate() + Vec2::Y * -20.0;
             let vel = Vec2::Y * -1000.0;
-            commands.spawn(ProjectileBundle::new(pos, vel, 1.0, *faction));
+            projectiles.spawn(pos, vel, 1.0, *faction);
+            fire_events.send(FireEvent {
+                entity,
+                faction: *faction,
+                pos,
+                dir: Vec2::NEG_Y,
+            });
         }
 
         // Set a new cooldown_expires.
-        let variance_range = ai.variance.end() - ai.variance.start();
-        let variance = ai.variance.start() + (fastrand::f32() * variance_range);
+        let variance_range = ai.max_variance - ai.min_variance;
+        let variance = ai.min_variance + (fastrand::f32() * variance_range);
         ai.cooldown_expires = now + ai.cooldown + variance;
     }
 }
@@ -0,0 +1,221 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    game::enemies::Enemy,
+    physics::{groups, Velocity},
+};
+
+// Number of segments used to approximate round arenas.
+const CIRCLE_SEGMENTS: u32 = 64;
+
+pub struct ArenaPlugin;
+
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Arena>()
+            .register_type::<BoundsBehavior>()
+            .add_systems(Update, spawn_arena_walls.run_if(resource_changed::<Arena>()))
+            .add_systems(PostUpdate, cull_enemies);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum ArenaShape {
+    Rect { size: Vec2 },
+    Circle { radius: f32 },
+}
+
+/// The playable area, centered on the origin.
+#[derive(Resource)]
+pub struct Arena {
+    pub shape: ArenaShape,
+    /// How far outside the arena things can go before they're cleaned up.
+    pub cull_margin: f32,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self {
+            shape: ArenaShape::Rect { size: Vec2::new(2400.0, 1600.0) },
+            cull_margin: 200.0,
+        }
+    }
+}
+
+impl Arena {
+    pub fn contains(&self, pos: Vec2) -> bool {
+        match self.shape {
+            ArenaShape::Rect { size } => Rect::from_center_size(Vec2::ZERO, size).contains(pos),
+            ArenaShape::Circle { radius } => pos.length_squared() <= radius * radius,
+        }
+    }
+
+    /// Returns the closest point to pos that's inside the arena, shrunk by margin.
+    pub fn clamp(&self, pos: Vec2, margin: f32) -> Vec2 {
+        match self.shape {
+            ArenaShape::Rect { size } => {
+                let half_size = (size / 2.0 - margin).max(Vec2::ZERO);
+                pos.clamp(-half_size, half_size)
+            },
+            ArenaShape::Circle { radius } => pos.clamp_length_max((radius - margin).max(0.0)),
+        }
+    }
+
+    pub fn should_cull(&self, pos: Vec2) -> bool {
+        match self.shape {
+            ArenaShape::Rect { size } => !Rect::from_center_size(Vec2::ZERO, size).inset(self.cull_margin).contains(pos),
+            ArenaShape::Circle { radius } => pos.length() > radius + self.cull_margin,
+        }
+    }
+
+    /// Points around the edge of the arena, with the first point repeated at the end.
+    pub fn outline(&self) -> Vec<Vec2> {
+        match self.shape {
+            ArenaShape::Rect { size } => {
+                let half = size / 2.0;
+                vec![
+                    Vec2::new(-half.x, -half.y),
+                    Vec2::new(half.x, -half.y),
+                    Vec2::new(half.x, half.y),
+                    Vec2::new(-half.x, half.y),
+                    Vec2::new(-half.x, -half.y),
+                ]
+            },
+            ArenaShape::Circle { radius } => {
+                (0..=CIRCLE_SEGMENTS)
+                    .map(|i| Vec2::from_angle(TAU * i as f32 / CIRCLE_SEGMENTS as f32) * radius)
+                    .collect()
+            },
+        }
+    }
+
+    /// Reflects the velocity if it's carrying the position further out of the arena. Returns
+    /// whether it bounced.
+    pub fn bounce(&self, transform: &mut Transform, velocity: &mut Velocity) -> bool {
+        let pos = transform.translation.truncate();
+        let vel = &mut velocity.inner;
+        let bounced = match self.shape {
+            ArenaShape::Rect { size } => {
+                let bounds = Rect::from_center_size(Vec2::ZERO, size);
+                let mut bounced = false;
+                if (pos.x < bounds.min.x && vel.x < 0.0) || (pos.x > bounds.max.x && vel.x > 0.0) {
+                    vel.x = -vel.x;
+                    bounced = true;
+                }
+                if (pos.y < bounds.min.y && vel.y < 0.0) || (pos.y > bounds.max.y && vel.y > 0.0) {
+                    vel.y = -vel.y;
+                    bounced = true;
+                }
+                bounced
+            },
+            ArenaShape::Circle { radius } => {
+                let normal = pos.normalize_or_zero();
+                if pos.length() > radius && vel.dot(normal) > 0.0 {
+                    *vel -= 2.0 * vel.dot(normal) * normal;
+                    true
+                } else {
+                    false
+                }
+            },
+        };
+
+        if bounced {
+            transform.translation = self.clamp(pos, 0.0).extend(transform.translation.z);
+        }
+        bounced
+    }
+
+    /// Moves a position that left the arena to the opposite side. Returns whether it wrapped.
+    pub fn wrap(&self, transform: &mut Transform) -> bool {
+        let pos = transform.translation.truncate();
+        if self.contains(pos) {
+            return false;
+        }
+
+        let wrapped = match self.shape {
+            ArenaShape::Rect { size } => (pos + size / 2.0).rem_euclid(size) - size / 2.0,
+            ArenaShape::Circle { radius } => -pos.clamp_length_max(radius),
+        };
+        transform.translation = wrapped.extend(transform.translation.z);
+        true
+    }
+}
+
+/// What a projectile does when it reaches the edge of the arena.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Component, Reflect)]
+#[reflect(Component)]
+pub enum BoundsBehavior {
+    /// Keep going until it's far enough out to be cleaned up.
+    #[default]
+    Despawn,
+    /// Come back in on the opposite side.
+    Wrap,
+    /// Bounce off the edges forever. Use Ricochet for a limited number of bounces.
+    Bounce,
+}
+
+#[derive(Component)]
+pub struct ArenaWalls;
+
+#[derive(Bundle)]
+struct ArenaWallsBundle {
+    name: Name,
+    walls: ArenaWalls,
+    rigid_body: RigidBody,
+    collider: Collider,
+    layers: CollisionGroups,
+    shape: ShapeBundle,
+    stroke: Stroke,
+}
+
+impl ArenaWallsBundle {
+    fn new(arena: &Arena) -> Self {
+        let outline = arena.outline();
+        let shape = shapes::Polygon {
+            points: outline.clone(),
+            closed: true,
+        };
+        Self {
+            name: Name::new("ArenaWalls"),
+            walls: ArenaWalls,
+            rigid_body: RigidBody::Fixed,
+            collider: Collider::polyline(outline, None),
+            layers: CollisionGroups::new(groups::WORLD, groups::PLAYER | groups::ENEMY),
+            shape: ShapeBundle {
+                path: GeometryBuilder::build_as(&shape),
+                spatial: SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, -10.0)),
+                ..default()
+            },
+            stroke: Stroke::new(Color::GRAY, 4.0),
+        }
+    }
+}
+
+fn spawn_arena_walls(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    walls_q: Query<Entity, With<ArenaWalls>>,
+) {
+    for entity in walls_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.spawn(ArenaWallsBundle::new(&arena));
+}
+
+fn cull_enemies(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    enemy_q: Query<(Entity, &GlobalTransform), With<Enemy>>,
+) {
+    for (entity, transform) in enemy_q.iter() {
+        if arena.should_cull(transform.translation().truncate()) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
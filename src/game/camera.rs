@@ -1,8 +1,25 @@
 use bevy::prelude::*;
 use bevy::core_pipeline::bloom::BloomSettings;
 use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::render::camera::ScalingMode;
+use bevy::transform::TransformSystem;
+use bevy::window::PrimaryWindow;
 use bevy_egui::EguiContexts;
 
+use crate::{
+    game::{
+        combat::{DamageEvent, DeathEvent},
+        factions::Faction,
+    },
+    settings::{GameplaySettings, Settings},
+    GAME_SIZE,
+};
+
+// How much one line of scrolling multiplies the zoom by.
+pub(crate) const ZOOM_STEP: f32 = 1.1;
+// Roughly how many pixels a touchpad has to scroll to count as one line.
+pub(crate) const PIXELS_PER_LINE: f32 = 50.0;
+
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
@@ -10,13 +27,109 @@ impl Plugin for CameraPlugin {
         app
             .add_systems(Update, (
                 camera_control,
-            ));
+                add_trauma,
+                apply_bloom_setting,
+                apply_zoom_settings.run_if(resource_changed::<Settings>()),
+            ))
+            .add_systems(PostUpdate, (
+                follow_target,
+                auto_zoom,
+                apply_camera_rig,
+            ).chain().before(TransformSystem::TransformPropagate));
+    }
+}
+
+/// The entity the camera follows. If it has children, the camera zooms out to keep them in view.
+#[derive(Component)]
+pub struct CameraTarget;
+
+#[derive(Component)]
+pub struct CameraRig {
+    /// When disabled, the rig stops touching the camera's transform and zoom so something else
+    /// can control it.
+    pub enabled: bool,
+    /// Half the size of the area around the focus the target can move in without the camera
+    /// following.
+    pub deadzone: Vec2,
+    /// How quickly the camera catches up to the target. Higher is snappier.
+    pub damping: f32,
+    /// The zoom the player picked. Auto zoom can zoom out further than this but never in.
+    pub zoom: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    pub auto_zoom: bool,
+    /// Extra space to keep around the target's children when auto zooming.
+    pub zoom_padding: f32,
+    /// How quickly the zoom changes. Higher is snappier.
+    pub zoom_damping: f32,
+    /// Trauma added per point of damage taken by the player.
+    pub damage_trauma: f32,
+    /// Trauma added whenever something dies.
+    pub death_trauma: f32,
+    /// Trauma lost per second.
+    pub trauma_decay: f32,
+    /// Max offset in world units when trauma is at 1.0.
+    pub max_shake_offset: f32,
+    /// Max rotation in radians when trauma is at 1.0.
+    pub max_shake_angle: f32,
+    focus: Vec2,
+    trauma: f32,
+    // World position to keep under the cursor while zooming.
+    zoom_anchor: Option<Vec2>,
+}
+
+impl CameraRig {
+    /// Zoom limits come from the gameplay settings.
+    pub fn new(zoom: f32, settings: &GameplaySettings) -> Self {
+        let mut rig = Self {
+            enabled: true,
+            deadzone: Vec2::new(100.0, 60.0),
+            damping: 4.0,
+            zoom,
+            min_zoom: 0.0,
+            max_zoom: 0.0,
+            auto_zoom: true,
+            zoom_padding: 200.0,
+            zoom_damping: 2.0,
+            damage_trauma: 0.3,
+            death_trauma: 0.1,
+            trauma_decay: 1.0,
+            max_shake_offset: 30.0,
+            max_shake_angle: 0.05,
+            focus: Vec2::ZERO,
+            trauma: 0.0,
+            zoom_anchor: None,
+        };
+        rig.set_zoom_limits(settings);
+        rig
+    }
+
+    /// Takes the zoom limits from the gameplay settings and keeps the zoom within them.
+    pub fn set_zoom_limits(&mut self, settings: &GameplaySettings) {
+        self.min_zoom = settings.min_zoom;
+        // Keep the limits usable even if the settings file has them backwards.
+        self.max_zoom = settings.max_zoom.max(settings.min_zoom);
+        self.zoom = self.zoom.clamp(self.min_zoom, self.max_zoom);
+    }
+
+    pub fn focus(&self) -> Vec2 {
+        self.focus
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Adds screen shake. Trauma is capped at 1.0.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
     }
 }
 
 pub fn spawn_camera(
     commands: &mut Commands,
     scale: f32,
+    settings: &Settings,
 ) {
     commands.spawn((
         Camera2dBundle {
@@ -28,37 +141,190 @@ pub fn spawn_camera(
                 far: 1000.,
                 near: -1000.,
                 scale,
+                scaling_mode: ScalingMode::Fixed {
+                    width: GAME_SIZE.0,
+                    height: GAME_SIZE.1,
+                },
                 ..default()
             },
             ..default()
         },
         BloomSettings::default(),
+        CameraRig::new(scale, &settings.gameplay),
     ));
 }
 
 fn camera_control(
     mut egui_ctx: EguiContexts,
     mut wheel_events: EventReader<MouseWheel>,
-    mut camera_q: Query<(&mut OrthographicProjection, &mut Transform)>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<(&mut CameraRig, &Camera, &GlobalTransform)>,
 ) {
     if egui_ctx.ctx_mut().wants_pointer_input() {
         return;
     }
 
-    let Ok((mut projection, mut transform)) = camera_q.get_single_mut() else {
+    let Ok((mut rig, camera, camera_transform)) = camera_q.get_single_mut() else {
         return;
     };
+    if !rig.enabled {
+        return;
+    }
 
+    let mut lines = 0.0;
     for event in wheel_events.read() {
         // debug!("Mouse wheel event: {:?}", event);
         match event.unit {
-            MouseScrollUnit::Line => {
-                projection.scale += 0.1 * -event.y;
-                projection.scale = projection.scale.max(0.1);
+            MouseScrollUnit::Line => lines += event.y,
+            MouseScrollUnit::Pixel => lines += event.y / PIXELS_PER_LINE,
+        }
+    }
+    if lines == 0.0 {
+        return;
+    }
+
+    rig.zoom = (rig.zoom * ZOOM_STEP.powf(-lines)).clamp(rig.min_zoom, rig.max_zoom);
+    rig.zoom_anchor = window_q.get_single().ok()
+        .and_then(|window| window.cursor_position())
+        .and_then(|cursor_pos| camera.viewport_to_world_2d(camera_transform, cursor_pos));
+}
+
+fn add_trauma(
+    settings: Res<Settings>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventReader<DeathEvent>,
+    mut camera_q: Query<&mut CameraRig>,
+) {
+    let Ok(mut rig) = camera_q.get_single_mut() else {
+        damage_events.clear();
+        death_events.clear();
+        return;
+    };
+
+    let mut trauma = 0.0;
+    for event in damage_events.read() {
+        if event.faction == Faction::Player {
+            trauma += event.amount * rig.damage_trauma;
+        }
+    }
+    trauma += death_events.read().count() as f32 * rig.death_trauma;
+    rig.add_trauma(trauma * settings.gameplay.screen_shake);
+}
+
+fn apply_bloom_setting(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    camera_q: Query<(Entity, Option<&BloomSettings>), With<CameraRig>>,
+) {
+    for (entity, bloom) in camera_q.iter() {
+        match (settings.video.bloom, bloom.is_some()) {
+            (true, false) => {
+                commands.entity(entity).insert(BloomSettings::default());
             },
-            MouseScrollUnit::Pixel => todo!(),
+            (false, true) => {
+                commands.entity(entity).remove::<BloomSettings>();
+            },
+            _ => {},
         }
     }
+}
+
+fn apply_zoom_settings(settings: Res<Settings>, mut camera_q: Query<&mut CameraRig>) {
+    for mut rig in camera_q.iter_mut() {
+        rig.set_zoom_limits(&settings.gameplay);
+    }
+}
+
+fn follow_target(
+    time: Res<Time>,
+    target_q: Query<&Transform, (With<CameraTarget>, Without<CameraRig>)>,
+    mut camera_q: Query<&mut CameraRig>,
+) {
+    let (Ok(target_transform), Ok(mut rig)) = (target_q.get_single(), camera_q.get_single_mut()) else {
+        return;
+    };
+    if !rig.enabled {
+        return;
+    }
+
+    // Only move the focus far enough to keep the target inside the deadzone.
+    let target = target_transform.translation.truncate();
+    let offset = target - rig.focus;
+    let excess = offset - offset.clamp(-rig.deadzone, rig.deadzone);
+    let desired = rig.focus + excess;
+
+    let t = 1.0 - (-rig.damping * time.delta_seconds()).exp();
+    rig.focus = rig.focus.lerp(desired, t);
+}
+
+fn auto_zoom(
+    time: Res<Time>,
+    target_q: Query<&Children, With<CameraTarget>>,
+    child_q: Query<&GlobalTransform>,
+    mut camera_q: Query<(&mut CameraRig, &mut OrthographicProjection)>,
+) {
+    let Ok((mut rig, mut projection)) = camera_q.get_single_mut() else {
+        return;
+    };
+    if !rig.enabled {
+        return;
+    }
+
+    let mut scale = rig.zoom;
+    if rig.auto_zoom {
+        if let Ok(children) = target_q.get_single() {
+            // Find how far the swarm reaches from the camera's focus in each direction.
+            let extents = child_q.iter_many(children)
+                .map(|transform| (transform.translation().truncate() - rig.focus).abs())
+                .fold(Vec2::ZERO, Vec2::max);
+            let needed = (extents + rig.zoom_padding) * 2.0;
+            let view_size = projection.area.size() / projection.scale;
+            if view_size.x > 0.0 && view_size.y > 0.0 {
+                let fit = needed / view_size;
+                scale = scale.max(fit.max_element());
+            }
+        }
+    }
+    let scale = scale.clamp(rig.min_zoom, rig.max_zoom);
+
+    // Ease toward the new zoom.
+    let old_scale = projection.scale;
+    let t = 1.0 - (-rig.zoom_damping * time.delta_seconds()).exp();
+    projection.scale += (scale - old_scale) * t;
+
+    // Move the focus so the anchor stays at the same spot on screen.
+    if let Some(anchor) = rig.zoom_anchor {
+        if old_scale > 0.0 {
+            rig.focus = anchor + (rig.focus - anchor) * (projection.scale / old_scale);
+        }
+        if (scale - projection.scale).abs() < 0.001 {
+            rig.zoom_anchor = None;
+        }
+    }
+}
+
+fn apply_camera_rig(
+    time: Res<Time>,
+    mut camera_q: Query<(&mut CameraRig, &mut Transform)>,
+) {
+    let Ok((mut rig, mut transform)) = camera_q.get_single_mut() else {
+        return;
+    };
+    if !rig.enabled {
+        return;
+    }
+
+    let mut pos = rig.focus;
+    let mut angle = 0.0;
+    if rig.trauma > 0.0 {
+        let shake = rig.trauma * rig.trauma;
+        let offset = Vec2::new(fastrand::f32() * 2.0 - 1.0, fastrand::f32() * 2.0 - 1.0);
+        pos += offset * rig.max_shake_offset * shake;
+        angle = (fastrand::f32() * 2.0 - 1.0) * rig.max_shake_angle * shake;
+    }
+    transform.translation = pos.extend(transform.translation.z);
+    transform.rotation = Quat::from_rotation_z(angle);
 
-    // TODO: Debug keys to move camera around.
+    let decay = rig.trauma_decay * time.delta_seconds();
+    rig.trauma = (rig.trauma - decay).max(0.0);
 }
@@ -1,10 +1,12 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
 use crate::{
     game::{
         factions::Faction,
         health::Health,
-        projectiles::Projectile,
+        projectiles::{Pierce, Pooled, Projectile, Projectiles},
+        sprites::Archetype,
     },
     physics::{self, groups, ActiveCollisionTypes, ActiveEvents, CollisionEvent, Group},
 };
@@ -14,11 +16,44 @@ pub struct CombatPlugin;
 impl Plugin for CombatPlugin {
     fn build(&self, app: &mut App) {
         app
+            .add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<FireEvent>()
+            .register_type::<Faction>()
+            .register_type::<HitSpec>()
             .add_systems(Update, check_hits);
     }
 }
 
-#[derive(Component)]
+/// Sent whenever an entity loses health from a hit.
+#[derive(Event)]
+pub struct DamageEvent {
+    pub entity: Entity,
+    pub faction: Faction,
+    pub amount: f32,
+    pub pos: Vec2,
+}
+
+/// Sent whenever an entity fires a shot or volley.
+#[derive(Event)]
+pub struct FireEvent {
+    pub entity: Entity,
+    pub faction: Faction,
+    pub pos: Vec2,
+    pub dir: Vec2,
+}
+
+/// Sent when an entity's health reaches zero, right before it's despawned.
+#[derive(Event)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub faction: Faction,
+    pub archetype: Option<Archetype>,
+    pub pos: Vec2,
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
 pub struct HitSpec {
     pub damage: f32,
 }
@@ -92,54 +127,211 @@ impl HurtBoxBundle {
     }
 }
 
+/// Collisions to check for hits, and the events sent when a hit lands.
+#[derive(SystemParam)]
+pub struct HitEvents<'w, 's> {
+    collisions: EventReader<'w, 's, CollisionEvent>,
+    damage: EventWriter<'w, DamageEvent>,
+    death: EventWriter<'w, DeathEvent>,
+}
+
 pub fn check_hits(
     mut commands: Commands,
-    mut collisions: EventReader<CollisionEvent>,
-    hit_box_q: Query<(&HitSpec, &Faction)>,
-    projectile_q: Query<&Projectile>,
-    mut health_q: Query<(&mut Health, &Faction)>,
+    mut projectiles: Projectiles,
+    mut hit_events: HitEvents,
+    hit_box_q: Query<(&HitSpec, &Faction), Without<Pooled>>,
+    mut projectile_q: Query<Option<&mut Pierce>, With<Projectile>>,
+    mut health_q: Query<(&mut Health, &Faction, &GlobalTransform, Option<&Archetype>)>,
     name_q: Query<&Name>,
 ) {
     // Listen for collisions between a hit box and a health component from different factions.
+    let HitEvents { collisions, damage: damage_events, death: death_events } = &mut hit_events;
     for collision in collisions.read() {
         // info!("Collision event: {:?}", collision);
-        if let &CollisionEvent::Started(e1, e2, _flags) = collision {
-            // TODO: Deduplicate code in the branches.
-            if let (Ok((hit_spec, faction1)), Ok((mut health, faction2))) = (hit_box_q.get(e1), health_q.get_mut(e2)) {
-                if faction1 == faction2 {
-                    continue;
-                }
-
-                let lost = health.lose_health(hit_spec.damage);
-                let name = name_q.get(e2)
-                    .map(|name| name.as_str())
-                    .unwrap_or("[unnamed]");
-                debug!("Entity {} lost {} health", name, lost);
-                if health.current() == 0.0 {
-                    commands.entity(e2).despawn_recursive();
-                    debug!("Entity {} died!", name);
-                }
-                if projectile_q.contains(e1) {
-                    commands.entity(e1).despawn_recursive();
-                }
-            } else if let (Ok((hit_spec, faction2)), Ok((mut health, faction1))) = (hit_box_q.get(e2), health_q.get_mut(e1)) {
-                if faction1 == faction2 {
-                    continue;
-                }
-
-                let lost = health.lose_health(hit_spec.damage);
-                let name = name_q.get(e1)
-                    .map(|name| name.as_str())
-                    .unwrap_or("[unnamed]");
-                debug!("Entity {} lost {} health", name, lost);
-                if health.current() == 0.0 {
-                    commands.entity(e1).despawn_recursive();
-                    debug!("Entity {} died!", name);
-                }
-                if projectile_q.contains(e2) {
-                    commands.entity(e2).despawn_recursive();
-                }
+        let &CollisionEvent::Started(e1, e2, _flags) = collision else {
+            continue;
+        };
+
+        // Figure out which entity is doing the hitting.
+        let (hitter, target) = if hit_box_q.contains(e1) && health_q.contains(e2) {
+            (e1, e2)
+        } else if hit_box_q.contains(e2) && health_q.contains(e1) {
+            (e2, e1)
+        } else {
+            continue;
+        };
+        let Ok((hit_spec, hit_faction)) = hit_box_q.get(hitter) else {
+            continue;
+        };
+        let Ok((mut health, target_faction, target_transform, archetype)) = health_q.get_mut(target) else {
+            continue;
+        };
+        if hit_faction == target_faction {
+            continue;
+        }
+
+        let lost = health.lose_health(hit_spec.damage);
+        let name = name_q.get(target)
+            .map(|name| name.as_str())
+            .unwrap_or("[unnamed]");
+        debug!("Entity {} lost {} health", name, lost);
+        let pos = target_transform.translation().truncate();
+        if lost > 0.0 {
+            damage_events.send(DamageEvent {
+                entity: target,
+                faction: *target_faction,
+                amount: lost,
+                pos,
+            });
+        }
+        // Only the hit that takes the last of the health counts as the killing blow.
+        if lost > 0.0 && health.current() == 0.0 {
+            commands.entity(target).despawn_recursive();
+            death_events.send(DeathEvent {
+                entity: target,
+                faction: *target_faction,
+                archetype: archetype.copied(),
+                pos,
+            });
+            debug!("Entity {} died!", name);
+        }
+
+        // Projectiles are used up on hit unless they can pierce.
+        if let Ok(pierce) = projectile_q.get_mut(hitter) {
+            match pierce {
+                Some(mut pierce) if pierce.remaining > 0 => pierce.remaining -= 1,
+                _ => projectiles.release(hitter),
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+    use bevy_rapier2d::rapier::geometry::CollisionEventFlags;
+
+    use super::*;
+    use crate::game::{
+        arena::Arena,
+        enemies::EnemyBundle,
+        headless,
+        projectiles::ProjectilesPlugin,
+        units::{BasicShooterBundle, UnitKind},
+    };
+
+    fn test_app() -> App {
+        let mut app = headless::minimal_app();
+        app
+            // Collisions are sent by hand instead of running physics.
+            .add_event::<CollisionEvent>()
+            .init_resource::<Arena>()
+            .add_plugins((
+                CombatPlugin,
+                ProjectilesPlugin,
+            ));
+        app
+    }
+
+    fn fire(app: &mut App, damage: f32, faction: Faction) -> Entity {
+        let mut state = SystemState::<Projectiles>::new(&mut app.world);
+        let entity = state.get_mut(&mut app.world)
+            .spawn(Vec2::ZERO, Vec2::ZERO, damage, faction)
+            .id();
+        state.apply(&mut app.world);
+        entity
+    }
+
+    fn collide(app: &mut App, e1: Entity, e2: Entity) {
+        app.world.send_event(CollisionEvent::Started(e1, e2, CollisionEventFlags::SENSOR));
+        app.update();
+    }
+
+    fn is_released(app: &App, projectile: Entity) -> bool {
+        let entity = app.world.entity(projectile);
+        entity.contains::<Pooled>() && !entity.contains::<Projectile>()
+    }
+
+    #[test]
+    fn player_shot_kills_enemy() {
+        let mut app = test_app();
+        let enemy = app.world.spawn(EnemyBundle::new(Vec2::ZERO)).id();
+        let projectile = fire(&mut app, 1.0, Faction::Player);
+
+        collide(&mut app, projectile, enemy);
+
+        assert!(app.world.get_entity(enemy).is_none());
+        assert!(is_released(&app, projectile));
+        assert_eq!(app.world.resource::<Events<DeathEvent>>().len(), 1);
+    }
+
+    #[test]
+    fn hits_work_in_either_order() {
+        let mut app = test_app();
+        let enemy = app.world.spawn(EnemyBundle::boss(Vec2::ZERO)).id();
+        let projectile = fire(&mut app, 1.0, Faction::Player);
+
+        collide(&mut app, enemy, projectile);
+
+        assert_eq!(app.world.get::<Health>(enemy).unwrap().current(), 39.0);
+        assert!(is_released(&app, projectile));
+    }
+
+    #[test]
+    fn same_faction_is_ignored() {
+        let mut app = test_app();
+        let enemy = app.world.spawn(EnemyBundle::new(Vec2::ZERO)).id();
+        let projectile = fire(&mut app, 1.0, Faction::Enemy);
+
+        collide(&mut app, projectile, enemy);
+
+        assert_eq!(app.world.get::<Health>(enemy).unwrap().current(), 1.0);
+        assert!(app.world.entity(projectile).contains::<Projectile>());
+        assert!(!app.world.entity(projectile).contains::<Pooled>());
+        assert!(app.world.resource::<Events<DamageEvent>>().is_empty());
+    }
+
+    #[test]
+    fn enemy_shot_hurts_shooter() {
+        let mut app = test_app();
+        let shooter = app.world.spawn(BasicShooterBundle::new(Vec2::ZERO, UnitKind::Tank)).id();
+        let projectile = fire(&mut app, 1.0, Faction::Enemy);
+
+        collide(&mut app, projectile, shooter);
+
+        assert_eq!(app.world.get::<Health>(shooter).unwrap().current(), 2.0);
+        assert!(is_released(&app, projectile));
+        assert_eq!(app.world.resource::<Events<DamageEvent>>().len(), 1);
+        assert!(app.world.resource::<Events<DeathEvent>>().is_empty());
+    }
+
+    #[test]
+    fn piercing_shot_is_used_up_after_last_target() {
+        let mut app = test_app();
+        let first = app.world.spawn(EnemyBundle::new(Vec2::ZERO)).id();
+        let second = app.world.spawn(EnemyBundle::new(Vec2::ZERO)).id();
+        let projectile = fire(&mut app, 1.0, Faction::Player);
+        app.world.entity_mut(projectile).insert(Pierce { remaining: 1 });
+
+        collide(&mut app, projectile, first);
+        assert!(app.world.get_entity(first).is_none());
+        assert_eq!(app.world.get::<Pierce>(projectile).unwrap().remaining, 0);
+        assert!(!is_released(&app, projectile));
+
+        collide(&mut app, projectile, second);
+        assert!(app.world.get_entity(second).is_none());
+        assert!(is_released(&app, projectile));
+    }
+
+    #[test]
+    fn released_projectile_does_not_hit_again() {
+        let mut app = test_app();
+        let enemy = app.world.spawn(EnemyBundle::boss(Vec2::ZERO)).id();
+        let projectile = fire(&mut app, 1.0, Faction::Player);
+
+        collide(&mut app, projectile, enemy);
+        collide(&mut app, projectile, enemy);
+
+        assert_eq!(app.world.get::<Health>(enemy).unwrap().current(), 39.0);
+    }
+}
@@ -5,7 +5,9 @@ use crate::{
     game::{
         combat::HurtBoxBundle,
         factions::Faction,
+        feedback::DamageFeedback,
         health::Health,
+        sprites::Archetype,
     },
     physics::groups,
 };
@@ -14,19 +16,30 @@ pub struct EnemiesPlugin;
 
 impl Plugin for EnemiesPlugin {
     fn build(&self, app: &mut App) {
+        app
+            .register_type::<Enemy>()
+            .register_type::<Boss>();
     }
 }
 
-#[derive(Component)]
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
 pub struct Enemy {
 }
 
+/// Marks the enemy whose health is shown on the HUD's boss bar.
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Boss;
+
 #[derive(Bundle)]
 pub struct EnemyBundle {
     name: Name,
     enemy: Enemy,
+    archetype: Archetype,
     faction: Faction,
     health: Health,
+    feedback: DamageFeedback,
     hurt_box: HurtBoxBundle,
     shape: ShapeBundle,
     fill: Fill,
@@ -34,26 +47,42 @@ pub struct EnemyBundle {
 
 impl EnemyBundle {
     pub fn new(pos: Vec2) -> Self {
+        Self::with_size(pos, 20.0, 1.0)
+    }
+
+    /// A bigger, tougher enemy for boss waves. Add Boss to show its health on the HUD.
+    pub fn boss(pos: Vec2) -> Self {
+        Self {
+            name: Name::new("Boss"),
+            archetype: Archetype::Boss,
+            ..Self::with_size(pos, 80.0, 40.0)
+        }
+    }
+
+    fn with_size(pos: Vec2, radius: f32, health: f32) -> Self {
         let shape = shapes::RegularPolygon {
             sides: 4,
-            feature: RegularPolygonFeature::Radius(20.0),
+            feature: RegularPolygonFeature::Radius(radius),
             ..default()
         };
-        let size = Vec2::splat(40.0);
+        let size = Vec2::splat(radius * 2.0);
         let transform = Transform::from_translation(pos.extend(0.0));
+        let color = Color::SILVER * 4.0;
         Self {
             name: Name::new("Enemy"),
             enemy: Enemy {
             },
+            archetype: Archetype::Enemy,
             faction: Faction::Enemy,
-            health: Health::new(1.0),
+            health: Health::new(health),
+            feedback: DamageFeedback::new(color),
             hurt_box: HurtBoxBundle::rect(size, groups::ENEMY),
             shape: ShapeBundle {
                 path: GeometryBuilder::build_as(&shape),
                 spatial: SpatialBundle::from_transform(transform),
                 ..default()
             },
-            fill: Fill::color(Color::SILVER * 4.0),
+            fill: Fill::color(color),
         }
     }
 }
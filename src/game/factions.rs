@@ -1,7 +1,9 @@
 use bevy::prelude::*;
 
-#[derive(Clone, Copy, PartialEq, Eq, Component)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Component, Reflect)]
+#[reflect(Component)]
 pub enum Faction {
+    #[default]
     Player,
     Enemy,
 }
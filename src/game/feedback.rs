@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::game::{
+    combat::DamageEvent,
+    health::Health,
+    sprites::SpriteVisual,
+};
+
+// How long the white flash lasts, in seconds.
+const FLASH_DURATION: f32 = 0.1;
+// How long the squash and stretch lasts, in seconds.
+const SQUASH_DURATION: f32 = 0.2;
+// How much the shape stretches at the start of a squash.
+const SQUASH_AMOUNT: f32 = 0.35;
+const DAMAGE_NUMBER_LIFETIME: f32 = 0.8;
+const DAMAGE_NUMBER_SPEED: f32 = 120.0;
+const DAMAGE_NUMBER_SIZE: f32 = 48.0;
+// Damage numbers are drawn above shapes and particles.
+const DAMAGE_NUMBER_Z: f32 = 20.0;
+
+pub struct FeedbackPlugin;
+
+impl Plugin for FeedbackPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                start_feedback,
+                update_feedback,
+                update_damage_numbers,
+            ).chain());
+    }
+}
+
+/// Flashes and squashes the entity's shape or sprite when it takes damage, and tints it as it loses health.
+#[derive(Component)]
+pub struct DamageFeedback {
+    pub color: Color,
+    pub low_health_color: Color,
+    pub flash_color: Color,
+    flash: f32,
+    squash: f32,
+}
+
+impl DamageFeedback {
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            low_health_color: Color::rgb(1.0, 0.1, 0.1) * 4.0,
+            flash_color: Color::WHITE * 6.0,
+            flash: 0.0,
+            squash: 0.0,
+        }
+    }
+
+    /// The colour for the given health before any flash.
+    fn tint(&self, health: &Health) -> Color {
+        let t = if health.max() > 0.0 {
+            health.missing() / health.max()
+        } else {
+            0.0
+        };
+        lerp_color(self.color, self.low_health_color, t)
+    }
+}
+
+#[derive(Component)]
+struct DamageNumber {
+    age: f32,
+}
+
+fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    let a = Vec4::from(a.as_rgba_f32());
+    let b = Vec4::from(b.as_rgba_f32());
+    Color::from(a.lerp(b, t.clamp(0.0, 1.0)))
+}
+
+fn start_feedback(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    mut feedback_q: Query<&mut DamageFeedback>,
+) {
+    for event in damage_events.read() {
+        if let Ok(mut feedback) = feedback_q.get_mut(event.entity) {
+            feedback.flash = FLASH_DURATION;
+            feedback.squash = SQUASH_DURATION;
+        }
+
+        commands.spawn((
+            Name::new("DamageNumber"),
+            DamageNumber {
+                age: 0.0,
+            },
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("{}", event.amount),
+                    TextStyle {
+                        font_size: DAMAGE_NUMBER_SIZE,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_translation(event.pos.extend(DAMAGE_NUMBER_Z)),
+                ..default()
+            },
+        ));
+    }
+}
+
+fn update_feedback(
+    time: Res<Time>,
+    mut feedback_q: Query<(&mut DamageFeedback, &Health, &mut Fill, &mut Transform, Option<&SpriteVisual>)>,
+    mut sprite_q: Query<&mut TextureAtlasSprite>,
+) {
+    let dt = time.delta_seconds();
+    for (mut feedback, health, mut fill, mut transform, visual) in feedback_q.iter_mut() {
+        let flash = (feedback.flash / FLASH_DURATION).clamp(0.0, 1.0);
+        let color = lerp_color(feedback.tint(health), feedback.flash_color, flash);
+        // Changing the fill rebuilds the shape's mesh, so only touch it when needed.
+        if fill.color != color {
+            fill.color = color;
+        }
+        // The shape is hidden while a sprite stands in for it, so tint the sprite too.
+        if let Some(mut sprite) = visual.and_then(|visual| sprite_q.get_mut(visual.sprite()).ok()) {
+            if sprite.color != color {
+                sprite.color = color;
+            }
+        }
+
+        // Stretch sideways and squash vertically, easing back to normal.
+        let squash = (feedback.squash / SQUASH_DURATION).clamp(0.0, 1.0);
+        let stretch = SQUASH_AMOUNT * squash * squash;
+        let scale = Vec3::new(1.0 + stretch, 1.0 - stretch, 1.0);
+        if transform.scale != scale {
+            transform.scale = scale;
+        }
+
+        feedback.flash = (feedback.flash - dt).max(0.0);
+        feedback.squash = (feedback.squash - dt).max(0.0);
+    }
+}
+
+fn update_damage_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut number_q: Query<(Entity, &mut DamageNumber, &mut Transform, &mut Text)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut number, mut transform, mut text) in number_q.iter_mut() {
+        number.age += dt;
+        if number.age >= DAMAGE_NUMBER_LIFETIME {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let t = number.age / DAMAGE_NUMBER_LIFETIME;
+        transform.translation.y += DAMAGE_NUMBER_SPEED * (1.0 - t) * dt;
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(1.0 - t * t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{factions::Faction, headless};
+
+    const COLOR: Color = Color::rgb(0.2, 0.4, 0.8);
+
+    fn test_app() -> App {
+        let mut app = headless::minimal_app();
+        app
+            .add_event::<DamageEvent>()
+            .add_plugins(FeedbackPlugin);
+        app
+    }
+
+    fn spawn_with_sprite(app: &mut App) -> (Entity, Entity) {
+        let sprite = app.world.spawn(TextureAtlasSprite::default()).id();
+        let entity = app.world.spawn((
+            DamageFeedback::new(COLOR),
+            Health::new(10.0),
+            Fill::color(COLOR),
+            Transform::default(),
+            SpriteVisual::new(sprite, None),
+        )).id();
+        (entity, sprite)
+    }
+
+    fn sprite_color(app: &App, sprite: Entity) -> Color {
+        app.world.get::<TextureAtlasSprite>(sprite).unwrap().color
+    }
+
+    #[test]
+    fn sprite_flashes_on_damage() {
+        let mut app = test_app();
+        let (entity, sprite) = spawn_with_sprite(&mut app);
+        app.world.send_event(DamageEvent {
+            entity,
+            faction: Faction::Enemy,
+            amount: 1.0,
+            pos: Vec2::ZERO,
+        });
+        app.update();
+
+        let flash_color = app.world.get::<DamageFeedback>(entity).unwrap().flash_color;
+        assert_eq!(sprite_color(&app, sprite), flash_color);
+        assert_eq!(app.world.get::<Fill>(entity).unwrap().color, flash_color);
+    }
+
+    #[test]
+    fn sprite_returns_to_tint_after_flash() {
+        let mut app = test_app();
+        let (entity, sprite) = spawn_with_sprite(&mut app);
+        app.world.send_event(DamageEvent {
+            entity,
+            faction: Faction::Enemy,
+            amount: 1.0,
+            pos: Vec2::ZERO,
+        });
+        for _ in 0..20 {
+            app.update();
+        }
+
+        assert_eq!(sprite_color(&app, sprite), COLOR);
+    }
+}
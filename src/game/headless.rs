@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::scene::ScenePlugin;
+use bevy::time::TimeUpdateStrategy;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    game::{
+        input::PlayerInput,
+        run::Run,
+        score::Score,
+        units::SwarmParent,
+        waves::WavesManager,
+        GamePlugin,
+    },
+    physics,
+};
+
+/// Each update advances time by this much, so a headless run plays out the same no matter how
+/// fast the machine is.
+pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Builds an app with only the gameplay and physics: no window, rendering, audio or UI. Drive it
+/// with App::update() or run_scripted().
+pub fn headless_app() -> App {
+    let mut app = minimal_app();
+    app
+        // Rapier looks for scene and mesh assets to build colliders from.
+        .add_plugins(ScenePlugin)
+        .insert_resource(RapierConfiguration {
+            gravity: Vec2::ZERO,
+            ..default()
+        })
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(30.0))
+        .add_plugins((
+            physics::PhysicsPlugin,
+            GamePlugin::headless(),
+        ));
+    app
+}
+
+/// Builds an app with just enough of the engine for gameplay plugins to run, stepping time by
+/// TICK each update. Add the plugins under test on top.
+pub fn minimal_app() -> App {
+    let mut app = App::new();
+    app
+        .add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            TransformPlugin,
+            HierarchyPlugin,
+        ))
+        // Vector shapes and projectiles still create meshes and materials, they just aren't drawn.
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(TICK));
+    app
+}
+
+/// Updates the app this many times, setting the swarm's PlayerInput from the script before each
+/// update. The script is given the tick number, starting at 0. The swarm is spawned on the app's
+/// first update, so input for that tick is ignored.
+pub fn run_scripted(
+    app: &mut App,
+    ticks: u32,
+    mut script: impl FnMut(u32) -> PlayerInput,
+) {
+    let mut input_q = app.world.query_filtered::<&mut PlayerInput, With<SwarmParent>>();
+    for tick in 0..ticks {
+        let input = script(tick);
+        if let Ok(mut swarm_input) = input_q.get_single_mut(&mut app.world) {
+            *swarm_input = input;
+        }
+        app.update();
+    }
+}
+
+/// Plays the game headless for a while, strafing side to side and shooting, and prints how it went.
+pub fn simulate(ticks: u32) {
+    let mut app = headless_app();
+    // Change direction every two seconds.
+    let strafe_ticks = (2.0 / TICK.as_secs_f32()) as u32;
+    run_scripted(&mut app, ticks, |tick| {
+        let direction = if (tick / strafe_ticks) % 2 == 0 { 1.0 } else { -1.0 };
+        PlayerInput {
+            movement: Vec2::X * direction,
+            shoot: true,
+            ..default()
+        }
+    });
+
+    let world = &mut app.world;
+    let swarm_size = world.query_filtered::<&Children, With<SwarmParent>>()
+        .get_single(world)
+        .map_or(0, |children| children.len());
+    let run = world.resource::<Run>();
+    let waves = world.resource::<WavesManager>();
+    let score = world.resource::<Score>();
+    println!("Simulated {} ticks ({:.1}s of game time)", ticks, ticks as f32 * TICK.as_secs_f32());
+    println!("Swarm size: {}", swarm_size);
+    println!("Wave: {} ({} enemies left)", waves.wave(), waves.remaining());
+    println!("Score: {} from {} kills", score.points(), score.kills());
+    println!("Run over: {}", run.is_over());
+}
@@ -4,10 +4,13 @@ pub struct HealthPlugin;
 
 impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
+        app
+            .register_type::<Health>();
     }
 }
 
-#[derive(Component)]
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
 pub struct Health {
     current: f32,
     max: f32,
@@ -21,8 +24,9 @@ impl Health {
         }
     }
 
+    /// Starts at this much health instead of full, e.g. when loading a saved run.
     pub fn with_current(mut self, current: f32) -> Self {
-        self.current = current.min(self.max);
+        self.current = current.min(self.max).max(0.0);
         self
     }
 
@@ -38,10 +42,64 @@ impl Health {
         (self.max - self.current).max(0.0)
     }
 
-    /// Returns how much health was actually lost.
+    /// Raises max health and heals by the same amount.
+    pub fn add_max(&mut self, amount: f32) {
+        self.max += amount;
+        self.current += amount;
+    }
+
+    /// Returns how much health was actually lost. Negative amounts don't heal.
     pub fn lose_health(&mut self, amount: f32) -> f32 {
-        let lost = amount.min(self.current);
+        let lost = amount.min(self.current).max(0.0);
         self.current -= lost;
         lost
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lose_health_returns_amount_lost() {
+        let mut health = Health::new(3.0);
+        assert_eq!(health.lose_health(1.0), 1.0);
+        assert_eq!(health.current(), 2.0);
+        assert_eq!(health.missing(), 1.0);
+    }
+
+    #[test]
+    fn overkill_only_loses_what_is_left() {
+        let mut health = Health::new(3.0);
+        assert_eq!(health.lose_health(10.0), 3.0);
+        assert_eq!(health.current(), 0.0);
+    }
+
+    #[test]
+    fn nothing_lost_when_already_dead() {
+        let mut health = Health::new(1.0);
+        health.lose_health(1.0);
+        assert_eq!(health.lose_health(1.0), 0.0);
+        assert_eq!(health.current(), 0.0);
+    }
+
+    #[test]
+    fn zero_damage_loses_nothing() {
+        let mut health = Health::new(2.0);
+        assert_eq!(health.lose_health(0.0), 0.0);
+        assert_eq!(health.current(), 2.0);
+    }
+
+    #[test]
+    fn negative_damage_does_not_heal() {
+        let mut health = Health::new(2.0).with_current(1.0);
+        assert_eq!(health.lose_health(-5.0), 0.0);
+        assert_eq!(health.current(), 1.0);
+    }
+
+    #[test]
+    fn with_current_stays_in_range() {
+        assert_eq!(Health::new(2.0).with_current(5.0).current(), 2.0);
+        assert_eq!(Health::new(2.0).with_current(-1.0).current(), 0.0);
+    }
+}
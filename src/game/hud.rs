@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{
+    egui::{self, Align2, Color32, ProgressBar, RichText},
+    EguiContexts,
+};
+
+use crate::{
+    game::{
+        enemies::Boss,
+        health::Health,
+        patterns::PatternHandle,
+        score::Score,
+        units::SwarmParent,
+        waves::WavesManager,
+    },
+    DEFAULT_SCALE,
+    GAME_SIZE,
+};
+
+// Sizes are in egui points at the default window size and get scaled with the window.
+const MARGIN: f32 = 10.0;
+const TEXT_SIZE: f32 = 20.0;
+const SMALL_TEXT_SIZE: f32 = 14.0;
+const BOSS_BAR_WIDTH: f32 = 400.0;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, hud);
+    }
+}
+
+fn hud_text(text: impl Into<String>, size: f32) -> RichText {
+    RichText::new(text)
+        .color(Color32::WHITE)
+        .size(size)
+}
+
+fn weapon_name(
+    asset_server: &AssetServer,
+    pattern: Option<&PatternHandle>,
+) -> String {
+    pattern
+        .and_then(|pattern| asset_server.get_path(pattern.0.id()))
+        .and_then(|path| path.path().file_name().map(|name| name.to_string_lossy().into_owned()))
+        .map(|name| name.trim_end_matches(".pattern.ron").replace('_', " "))
+        .unwrap_or_else(|| "basic shot".to_string())
+}
+
+fn hud(
+    mut egui_ctx: EguiContexts,
+    asset_server: Res<AssetServer>,
+    waves: Res<WavesManager>,
+    score: Res<Score>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    swarm_q: Query<(&Children, Option<&PatternHandle>), With<SwarmParent>>,
+    boss_q: Query<&Health, With<Boss>>,
+) {
+    let ctx = egui_ctx.ctx_mut();
+
+    // Keep the HUD the same size relative to the window.
+    let default_height = GAME_SIZE.1 * DEFAULT_SCALE as f32;
+    let scale = window_q.get_single()
+        .map(|window| (window.height() / default_height).max(0.5))
+        .unwrap_or(1.0);
+    let margin = MARGIN * scale;
+
+    let (swarm_size, pattern) = swarm_q.get_single()
+        .map(|(children, pattern)| (children.len(), pattern))
+        .unwrap_or_default();
+
+    egui::Area::new("hud_swarm")
+        .anchor(Align2::LEFT_TOP, (margin, margin))
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.label(hud_text(format!("Swarm: {}", swarm_size), TEXT_SIZE * scale));
+            let wave_text = if waves.in_break() {
+                format!("Wave {} cleared", waves.wave())
+            } else {
+                format!("Wave {} - {} left", waves.wave(), waves.remaining())
+            };
+            ui.label(hud_text(wave_text, SMALL_TEXT_SIZE * scale));
+        });
+
+    egui::Area::new("hud_score")
+        .anchor(Align2::RIGHT_TOP, (-margin, margin))
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.with_layout(egui::Layout::top_down(egui::Align::Max), |ui| {
+                ui.label(hud_text(format!("{}", score.points()), TEXT_SIZE * scale));
+                ui.label(hud_text(format!("x{:.1}", score.multiplier()), SMALL_TEXT_SIZE * scale));
+            });
+        });
+
+    egui::Area::new("hud_weapon")
+        .anchor(Align2::LEFT_BOTTOM, (margin, -margin))
+        .interactable(false)
+        .show(ctx, |ui| {
+            let weapon = weapon_name(&asset_server, pattern);
+            ui.label(hud_text(format!("Weapon: {}", weapon), SMALL_TEXT_SIZE * scale));
+        });
+
+    if let Ok(health) = boss_q.get_single() {
+        egui::Area::new("hud_boss")
+            .anchor(Align2::CENTER_TOP, (0.0, margin))
+            .interactable(false)
+            .show(ctx, |ui| {
+                let fraction = if health.max() > 0.0 {
+                    health.current() / health.max()
+                } else {
+                    0.0
+                };
+                ui.add(ProgressBar::new(fraction)
+                    .desired_width(BOSS_BAR_WIDTH * scale)
+                    .fill(Color32::from_rgb(200, 40, 40))
+                    .text(hud_text("BOSS", SMALL_TEXT_SIZE * scale)));
+            });
+    }
+}
@@ -1,7 +1,13 @@
 use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
 use bevy::window::PrimaryWindow;
 use bevy_egui::EguiContexts;
 
+use crate::{
+    game::camera::CameraRig,
+    settings::Settings,
+};
+
 pub struct InputPlugin;
 
 impl Plugin for InputPlugin {
@@ -15,6 +21,7 @@ impl Plugin for InputPlugin {
 }
 
 #[derive(Default, Component, Reflect)]
+#[reflect(Component)]
 pub struct PlayerInput {
     pub movement: Vec2,
     pub aim: Vec2,
@@ -29,7 +36,7 @@ pub struct PlayerInput {
 // https://bevy-cheatbook.github.io/cookbook/cursor2world.html#2d-games
 pub fn get_mouse_world_pos(
     window_q: &Query<&Window, With<PrimaryWindow>>,
-    camera_q: &Query<(&Camera, &GlobalTransform)>,
+    camera_q: &Query<(&Camera, &GlobalTransform), With<CameraRig>>,
 ) -> Option<Vec2> {
     let (camera, camera_transform) = camera_q.single();
     let window = window_q.single();
@@ -47,16 +54,23 @@ pub enum AimDevice {
     Mouse(Vec2),
 }
 
+/// The state of every connected gamepad.
+#[derive(SystemParam)]
+pub struct GamepadInput<'w> {
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+}
+
 pub fn read_player_input(
+    settings: Res<Settings>,
     keys: Res<Input<KeyCode>>,
     mouse_buttons: Res<Input<MouseButton>>,
     mut cursor_moved: EventReader<CursorMoved>,
-    gamepads: Res<Gamepads>,
-    pad_buttons: Res<Input<GamepadButton>>,
-    axes: Res<Axis<GamepadAxis>>,
+    pads: GamepadInput,
     mut egui_ctx: EguiContexts,
     mut player_q: Query<(&mut PlayerInput, &GlobalTransform)>,
-    camera_q: Query<(&Camera, &GlobalTransform)>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<CameraRig>>,
     primary_window_q: Query<&Window, With<PrimaryWindow>>,
 ) {
     let Ok((mut input, player_transform)) = player_q.get_single_mut() else {
@@ -68,13 +82,14 @@ pub fn read_player_input(
     let mut aim_device = input.aim_device;
     let mut shoot = false;
     let mut reset_game = false;
+    let bindings = &settings.input;
 
     // Read input from gamepad.
-    if let Some(gamepad) = gamepads.iter().next() {
+    if let Some(gamepad) = pads.gamepads.iter().next() {
         // Movement
         let move_x = GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX);
         let move_y = GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY);
-        if let (Some(x), Some(y)) = (axes.get(move_x), axes.get(move_y)) {
+        if let (Some(x), Some(y)) = (pads.axes.get(move_x), pads.axes.get(move_y)) {
             let tmp = Vec2::new(x, y);
             // TODO: See if we can configure the deadzone using Bevy's APIs.
             if tmp.length() > 0.1 {
@@ -85,7 +100,7 @@ pub fn read_player_input(
         // Aim
         let aim_x = GamepadAxis::new(gamepad, GamepadAxisType::RightStickX);
         let aim_y = GamepadAxis::new(gamepad, GamepadAxisType::RightStickY);
-        if let (Some(x), Some(y)) = (axes.get(aim_x), axes.get(aim_y)) {
+        if let (Some(x), Some(y)) = (pads.axes.get(aim_x), pads.axes.get(aim_y)) {
             let tmp = Vec2::new(x, y);
             // TODO: See if we can configure the deadzone using Bevy's APIs.
             if tmp.length() > 0.1 {
@@ -98,17 +113,17 @@ pub fn read_player_input(
 
         // Shoot
         let shoot_button = GamepadButton::new(gamepad, GamepadButtonType::RightTrigger2);
-        shoot |= pad_buttons.pressed(shoot_button);
+        shoot |= pads.buttons.pressed(shoot_button);
 
         let reset_button = GamepadButton::new(gamepad, GamepadButtonType::Start);
-        reset_game |= pad_buttons.pressed(reset_button);
+        reset_game |= pads.buttons.pressed(reset_button);
     }
 
     // Read input from mouse/keyboard.
     // Movement
     if movement == Vec2::ZERO && !egui_ctx.ctx_mut().wants_keyboard_input() {
-        let x = (keys.pressed(KeyCode::D) as i8 - keys.pressed(KeyCode::A) as i8) as f32;
-        let y = (keys.pressed(KeyCode::W) as i8 - keys.pressed(KeyCode::S) as i8) as f32;
+        let x = (keys.pressed(bindings.move_right) as i8 - keys.pressed(bindings.move_left) as i8) as f32;
+        let y = (keys.pressed(bindings.move_up) as i8 - keys.pressed(bindings.move_down) as i8) as f32;
         movement = Vec2::new(x, y).normalize_or_zero();
     }
 
@@ -124,9 +139,9 @@ pub fn read_player_input(
     }
 
     // Shoot
-    shoot |= keys.pressed(KeyCode::Space) && !egui_ctx.ctx_mut().wants_keyboard_input();
+    shoot |= keys.pressed(bindings.shoot) && !egui_ctx.ctx_mut().wants_keyboard_input();
 
-    reset_game |= keys.just_pressed(KeyCode::Space) && !egui_ctx.ctx_mut().wants_keyboard_input();
+    reset_game |= keys.just_pressed(bindings.shoot) && !egui_ctx.ctx_mut().wants_keyboard_input();
 
     // Store results in player input component.
     input.movement = movement;
@@ -0,0 +1,296 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Align2, Color32, RichText},
+    EguiContexts,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::SoundEvent,
+    game::{
+        run::{run_over, RunEndedEvent, STARTING_SWARM_SIZE},
+        score::Score,
+        units::UnitKind,
+        waves::WavesManager,
+    },
+    persist::{self, PersistError},
+};
+
+const SAVE_FILENAME: &str = "save.ron";
+// Bump this and add a step to MIGRATIONS when changing MetaProgress' format.
+const SAVE_VERSION: u32 = 1;
+// MIGRATIONS[i] upgrades a save from version i + 1 to version i + 2.
+const MIGRATIONS: &[fn(&mut MetaProgress)] = &[];
+// Currency earned per point of score, and per wave reached.
+const CURRENCY_PER_POINT: f32 = 0.01;
+const CURRENCY_PER_WAVE: u64 = 10;
+const SWARM_SIZE_PER_LEVEL: u32 = 5;
+const MAX_SWARM_SIZE_LEVEL: u32 = 6;
+const SWARM_SIZE_BASE_COST: u64 = 200;
+
+pub struct MetaPlugin;
+
+impl Plugin for MetaPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(load_meta_progress())
+            .add_systems(Update, (
+                award_currency,
+                upgrade_shop.run_if(run_over),
+            ).chain());
+    }
+}
+
+/// Weapons the swarm can start a run with, on top of each shooter's basic shot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Weapon {
+    Fan,
+    Spiral,
+    HomingFan,
+}
+
+impl Weapon {
+    pub const ALL: [Self; 3] = [Self::Fan, Self::Spiral, Self::HomingFan];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Fan => "Fan",
+            Self::Spiral => "Spiral",
+            Self::HomingFan => "Homing Fan",
+        }
+    }
+
+    pub fn pattern_path(&self) -> &'static str {
+        match self {
+            Self::Fan => "patterns/fan.pattern.ron",
+            Self::Spiral => "patterns/spiral.pattern.ron",
+            Self::HomingFan => "patterns/homing_fan.pattern.ron",
+        }
+    }
+
+    fn cost(&self) -> u64 {
+        match self {
+            Self::Fan => 300,
+            Self::Spiral => 600,
+            Self::HomingFan => 1000,
+        }
+    }
+}
+
+fn unit_cost(kind: UnitKind) -> u64 {
+    match kind {
+        UnitKind::Basic => 0,
+        UnitKind::Tank => 400,
+        UnitKind::Gunner => 500,
+    }
+}
+
+/// Everything that carries over between runs.
+#[derive(Clone, Debug, Deserialize, Serialize, Resource)]
+#[serde(default)]
+pub struct MetaProgress {
+    pub version: u32,
+    pub currency: u64,
+    pub unlocked_units: Vec<UnitKind>,
+    pub unlocked_weapons: Vec<Weapon>,
+    pub swarm_size_level: u32,
+    pub selected_unit: UnitKind,
+    /// None means the swarm only has its basic shot.
+    pub selected_weapon: Option<Weapon>,
+    pub runs: u32,
+    /// Currency earned by the last finished run.
+    #[serde(skip)]
+    pub last_earned: u64,
+}
+
+impl Default for MetaProgress {
+    fn default() -> Self {
+        Self {
+            version: SAVE_VERSION,
+            currency: 0,
+            unlocked_units: vec![UnitKind::Basic],
+            unlocked_weapons: Vec::new(),
+            swarm_size_level: 0,
+            selected_unit: UnitKind::Basic,
+            selected_weapon: None,
+            runs: 0,
+            last_earned: 0,
+        }
+    }
+}
+
+impl MetaProgress {
+    fn migrate(mut self) -> Result<Self, PersistError> {
+        if self.version > SAVE_VERSION {
+            return Err(PersistError::UnsupportedVersion(self.version));
+        }
+        // Upgrade one version at a time so old saves go through every step.
+        self.version = self.version.max(1);
+        while self.version < SAVE_VERSION {
+            MIGRATIONS[self.version as usize - 1](&mut self);
+            self.version += 1;
+        }
+
+        // Don't trust a hand-edited save to only select things that are unlocked.
+        if !self.unlocked_units.contains(&UnitKind::Basic) {
+            self.unlocked_units.insert(0, UnitKind::Basic);
+        }
+        if !self.unlocked_units.contains(&self.selected_unit) {
+            self.selected_unit = UnitKind::Basic;
+        }
+        if self.selected_weapon.is_some_and(|weapon| !self.unlocked_weapons.contains(&weapon)) {
+            self.selected_weapon = None;
+        }
+        self.swarm_size_level = self.swarm_size_level.min(MAX_SWARM_SIZE_LEVEL);
+        Ok(self)
+    }
+
+    pub fn starting_swarm_size(&self) -> u32 {
+        STARTING_SWARM_SIZE + self.swarm_size_level * SWARM_SIZE_PER_LEVEL
+    }
+
+    /// The cost of the next swarm size upgrade, or None if it's maxed out.
+    fn swarm_size_cost(&self) -> Option<u64> {
+        (self.swarm_size_level < MAX_SWARM_SIZE_LEVEL)
+            .then_some(SWARM_SIZE_BASE_COST * (self.swarm_size_level as u64 + 1))
+    }
+
+    /// Spends currency if there's enough. Returns whether it was spent.
+    fn spend(&mut self, cost: u64) -> bool {
+        if self.currency < cost {
+            return false;
+        }
+        self.currency -= cost;
+        true
+    }
+}
+
+fn load_meta_progress() -> MetaProgress {
+    if cfg!(target_arch = "wasm32") {
+        return default();
+    }
+
+    let path = persist::config_path(SAVE_FILENAME);
+    match persist::load_ron::<MetaProgress>(&path).and_then(|meta| meta.map(MetaProgress::migrate).transpose()) {
+        Ok(meta) => meta.unwrap_or_default(),
+        Err(e) => {
+            warn!("Could not load save file from {}: {}", path.display(), e);
+            default()
+        },
+    }
+}
+
+fn save_meta_progress(meta: &MetaProgress) {
+    if cfg!(target_arch = "wasm32") {
+        return;
+    }
+    let path = persist::config_path(SAVE_FILENAME);
+    if let Err(e) = persist::save_ron(&path, meta) {
+        error!("Could not save progress: {}", e);
+    }
+}
+
+fn award_currency(
+    mut run_ended_events: EventReader<RunEndedEvent>,
+    score: Res<Score>,
+    waves: Res<WavesManager>,
+    mut meta: ResMut<MetaProgress>,
+) {
+    if run_ended_events.read().count() == 0 {
+        return;
+    }
+
+    let earned = (score.points() as f32 * CURRENCY_PER_POINT) as u64
+        + waves.wave() as u64 * CURRENCY_PER_WAVE;
+    meta.currency += earned;
+    meta.last_earned = earned;
+    meta.runs += 1;
+    save_meta_progress(&meta);
+}
+
+fn upgrade_shop(
+    mut egui_ctx: EguiContexts,
+    mut meta: ResMut<MetaProgress>,
+    mut sound_events: EventWriter<SoundEvent>,
+) {
+    let ctx = egui_ctx.ctx_mut();
+    // Edit a copy so the save file is only written when something actually changed.
+    let mut new_meta = meta.clone();
+
+    egui::Window::new("Upgrades")
+        .anchor(Align2::RIGHT_CENTER, (-10.0, 0.0))
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(RichText::new(format!("Currency: {}", new_meta.currency)).strong());
+            ui.label(RichText::new(format!("+{} this run", new_meta.last_earned)).color(Color32::YELLOW));
+
+            ui.separator();
+            ui.heading("Units");
+            for kind in UnitKind::ALL {
+                ui.horizontal(|ui| {
+                    if new_meta.unlocked_units.contains(&kind) {
+                        ui.selectable_value(&mut new_meta.selected_unit, kind, kind.name());
+                    } else {
+                        let cost = unit_cost(kind);
+                        let label = format!("Unlock {} ({})", kind.name(), cost);
+                        if ui.add_enabled(new_meta.currency >= cost, egui::Button::new(label)).clicked()
+                            && new_meta.spend(cost) {
+                            new_meta.unlocked_units.push(kind);
+                            new_meta.selected_unit = kind;
+                        }
+                    }
+                });
+            }
+
+            ui.separator();
+            ui.heading("Weapons");
+            ui.selectable_value(&mut new_meta.selected_weapon, None, "Basic Shot");
+            for weapon in Weapon::ALL {
+                ui.horizontal(|ui| {
+                    if new_meta.unlocked_weapons.contains(&weapon) {
+                        ui.selectable_value(&mut new_meta.selected_weapon, Some(weapon), weapon.name());
+                    } else {
+                        let cost = weapon.cost();
+                        let label = format!("Unlock {} ({})", weapon.name(), cost);
+                        if ui.add_enabled(new_meta.currency >= cost, egui::Button::new(label)).clicked()
+                            && new_meta.spend(cost) {
+                            new_meta.unlocked_weapons.push(weapon);
+                            new_meta.selected_weapon = Some(weapon);
+                        }
+                    }
+                });
+            }
+
+            ui.separator();
+            ui.heading("Swarm");
+            ui.label(format!("Starting size: {}", new_meta.starting_swarm_size()));
+            match new_meta.swarm_size_cost() {
+                Some(cost) => {
+                    let label = format!("+{} shooters ({})", SWARM_SIZE_PER_LEVEL, cost);
+                    if ui.add_enabled(new_meta.currency >= cost, egui::Button::new(label)).clicked()
+                        && new_meta.spend(cost) {
+                        new_meta.swarm_size_level += 1;
+                    }
+                },
+                None => {
+                    ui.label("Maxed out");
+                },
+            }
+        });
+
+    if new_meta.currency != meta.currency
+        || new_meta.selected_unit != meta.selected_unit
+        || new_meta.selected_weapon != meta.selected_weapon
+        || new_meta.swarm_size_level != meta.swarm_size_level {
+        // Buying something sounds different from just changing the selection.
+        let sound = if new_meta.currency != meta.currency {
+            SoundEvent::Pickup
+        } else {
+            SoundEvent::UiClick
+        };
+        sound_events.send(sound);
+        *meta = new_meta;
+        save_meta_progress(&meta);
+    }
+}
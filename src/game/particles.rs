@@ -0,0 +1,331 @@
+use std::f32::consts::TAU;
+use std::ops::Range;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+
+use crate::{
+    game::{
+        combat::{DamageEvent, DeathEvent, FireEvent},
+        factions::Faction,
+    },
+    physics::Velocity,
+};
+
+// Stop spawning particles past this many so a huge fight can't tank the frame rate.
+const MAX_PARTICLES: usize = 4000;
+// Particles are drawn above everything else.
+const PARTICLE_Z: f32 = 10.0;
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ParticleAssets>()
+            .init_resource::<ParticlePool>()
+            .add_systems(Update, (
+                combat_effects,
+                engine_trails,
+                update_particles,
+            ).chain());
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParticleColor {
+    PlayerFire,
+    EnemyFire,
+    Spark,
+    Explosion,
+    Trail,
+}
+
+impl ParticleColor {
+    const ALL: [Self; 5] = [Self::PlayerFire, Self::EnemyFire, Self::Spark, Self::Explosion, Self::Trail];
+
+    // Values above 1.0 glow with the camera's bloom.
+    fn color(&self) -> Color {
+        match self {
+            Self::PlayerFire => Color::CYAN * 6.0,
+            Self::EnemyFire => Color::RED * 6.0,
+            Self::Spark => Color::WHITE * 8.0,
+            Self::Explosion => Color::ORANGE * 6.0,
+            Self::Trail => Color::ORANGE * 2.0,
+        }
+    }
+}
+
+/// A batch of particles flying out from a point.
+#[derive(Clone, Debug)]
+pub struct Burst {
+    pub count: u32,
+    pub color: ParticleColor,
+    pub speed: Range<f32>,
+    pub lifetime: Range<f32>,
+    pub size: Range<f32>,
+    /// Fraction of speed lost per second.
+    pub drag: f32,
+    /// Particles fly within this many radians either side of the direction. Ignored when the
+    /// direction is zero.
+    pub spread: f32,
+}
+
+impl Burst {
+    pub fn muzzle_flash(faction: Faction) -> Self {
+        Self {
+            count: 4,
+            color: match faction {
+                Faction::Player => ParticleColor::PlayerFire,
+                Faction::Enemy => ParticleColor::EnemyFire,
+            },
+            speed: 100.0..300.0,
+            lifetime: 0.05..0.12,
+            size: 3.0..6.0,
+            drag: 8.0,
+            spread: 0.5,
+        }
+    }
+
+    pub fn hit_sparks() -> Self {
+        Self {
+            count: 8,
+            color: ParticleColor::Spark,
+            speed: 200.0..600.0,
+            lifetime: 0.1..0.25,
+            size: 2.0..4.0,
+            drag: 6.0,
+            spread: TAU,
+        }
+    }
+
+    pub fn explosion() -> Self {
+        Self {
+            count: 32,
+            color: ParticleColor::Explosion,
+            speed: 50.0..500.0,
+            lifetime: 0.3..0.7,
+            size: 4.0..10.0,
+            drag: 3.0,
+            spread: TAU,
+        }
+    }
+
+    pub fn engine_trail() -> Self {
+        Self {
+            count: 1,
+            color: ParticleColor::Trail,
+            speed: 20.0..60.0,
+            lifetime: 0.2..0.4,
+            size: 3.0..5.0,
+            drag: 2.0,
+            spread: 0.4,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Particle {
+    velocity: Vec2,
+    drag: f32,
+    size: f32,
+    lifetime: f32,
+    age: f32,
+}
+
+/// Leaves a trail of particles behind the entity while it moves.
+#[derive(Component)]
+pub struct EngineTrail {
+    /// Seconds between particles.
+    pub interval: f32,
+    /// Below this speed no particles are spawned.
+    pub min_speed: f32,
+    cooldown: f32,
+}
+
+impl EngineTrail {
+    pub fn new(interval: f32) -> Self {
+        Self {
+            interval,
+            min_speed: 50.0,
+            cooldown: fastrand::f32() * interval,
+        }
+    }
+}
+
+/// Mesh and materials shared by every particle.
+#[derive(Resource)]
+pub struct ParticleAssets {
+    mesh: Mesh2dHandle,
+    materials: Vec<Handle<ColorMaterial>>,
+}
+
+impl ParticleAssets {
+    fn material(&self, color: ParticleColor) -> Handle<ColorMaterial> {
+        let i = ParticleColor::ALL.iter().position(|&c| c == color).unwrap();
+        self.materials[i].clone()
+    }
+}
+
+impl FromWorld for ParticleAssets {
+    fn from_world(world: &mut World) -> Self {
+        // A unit square, scaled to each particle's size.
+        let mesh = world.resource_mut::<Assets<Mesh>>()
+            .add(shape::Quad::new(Vec2::ONE).into());
+        let mut materials = world.resource_mut::<Assets<ColorMaterial>>();
+        Self {
+            mesh: mesh.into(),
+            materials: ParticleColor::ALL.iter()
+                .map(|color| materials.add(ColorMaterial::from(color.color())))
+                .collect(),
+        }
+    }
+}
+
+/// Keeps track of hidden particle entities that can be reused.
+#[derive(Default, Resource)]
+pub struct ParticlePool {
+    free: Vec<Entity>,
+    active: usize,
+}
+
+impl ParticlePool {
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn pooled(&self) -> usize {
+        self.free.len()
+    }
+}
+
+/// Spawns particles out of the pool.
+#[derive(SystemParam)]
+pub struct Particles<'w, 's> {
+    commands: Commands<'w, 's>,
+    pool: ResMut<'w, ParticlePool>,
+    assets: Res<'w, ParticleAssets>,
+}
+
+impl<'w, 's> Particles<'w, 's> {
+    /// Spawns a burst of particles at pos, heading in dir.
+    pub fn burst(&mut self, pos: Vec2, dir: Vec2, burst: &Burst) {
+        let base_angle = dir.y.atan2(dir.x);
+        for _ in 0..burst.count {
+            if self.pool.active >= MAX_PARTICLES {
+                return;
+            }
+
+            let angle = if dir == Vec2::ZERO {
+                fastrand::f32() * TAU
+            } else {
+                base_angle + (fastrand::f32() * 2.0 - 1.0) * burst.spread
+            };
+            let particle = Particle {
+                velocity: Vec2::from_angle(angle) * random_in(&burst.speed),
+                drag: burst.drag,
+                size: random_in(&burst.size),
+                lifetime: random_in(&burst.lifetime),
+                age: 0.0,
+            };
+            self.spawn(pos, particle, burst.color);
+        }
+    }
+
+    fn spawn(&mut self, pos: Vec2, particle: Particle, color: ParticleColor) {
+        self.pool.active += 1;
+        let transform = Transform::from_translation(pos.extend(PARTICLE_Z))
+            .with_scale(Vec3::splat(particle.size));
+        let material = self.assets.material(color);
+        if let Some(entity) = self.pool.free.pop() {
+            self.commands.entity(entity).insert((
+                particle,
+                transform,
+                Visibility::Inherited,
+                material,
+            ));
+        } else {
+            self.commands.spawn((
+                Name::new("Particle"),
+                particle,
+                ColorMesh2dBundle {
+                    mesh: self.assets.mesh.clone(),
+                    material,
+                    transform,
+                    ..default()
+                },
+            ));
+        }
+    }
+}
+
+fn random_in(range: &Range<f32>) -> f32 {
+    range.start + fastrand::f32() * (range.end - range.start)
+}
+
+fn combat_effects(
+    mut particles: Particles,
+    mut fire_events: EventReader<FireEvent>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventReader<DeathEvent>,
+) {
+    for event in fire_events.read() {
+        particles.burst(event.pos, event.dir, &Burst::muzzle_flash(event.faction));
+    }
+    for event in damage_events.read() {
+        particles.burst(event.pos, Vec2::ZERO, &Burst::hit_sparks());
+    }
+    for event in death_events.read() {
+        particles.burst(event.pos, Vec2::ZERO, &Burst::explosion());
+    }
+}
+
+fn engine_trails(
+    mut particles: Particles,
+    time: Res<Time>,
+    mut trail_q: Query<(&mut EngineTrail, &Velocity, &GlobalTransform)>,
+) {
+    let dt = time.delta_seconds();
+    let burst = Burst::engine_trail();
+    for (mut trail, velocity, transform) in trail_q.iter_mut() {
+        trail.cooldown -= dt;
+        if trail.cooldown > 0.0 {
+            continue;
+        }
+        trail.cooldown += trail.interval;
+        trail.cooldown = trail.cooldown.max(0.0);
+
+        if velocity.inner.length() < trail.min_speed {
+            continue;
+        }
+        particles.burst(transform.translation().truncate(), -velocity.inner, &burst);
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    mut particle_q: Query<(Entity, &mut Particle, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut particle, mut transform) in particle_q.iter_mut() {
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity)
+                .remove::<Particle>()
+                .insert(Visibility::Hidden);
+            pool.free.push(entity);
+            pool.active = pool.active.saturating_sub(1);
+            continue;
+        }
+
+        let drag = particle.drag;
+        particle.velocity *= (1.0 - drag * dt).max(0.0);
+        transform.translation += (particle.velocity * dt).extend(0.0);
+        // Shrink away instead of fading so every particle can share a material.
+        let remaining = 1.0 - particle.age / particle.lifetime;
+        transform.scale = Vec3::splat(particle.size * remaining);
+    }
+}
@@ -0,0 +1,268 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use bevy::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::Deserialize;
+
+use crate::game::{
+    combat::FireEvent,
+    factions::Faction,
+    health::Health,
+    input::PlayerInput,
+    projectiles::{ProjectileModifiers, Projectiles},
+};
+
+// Don't let a bad pattern file spawn projectiles every frame.
+const MIN_INTERVAL: f32 = 0.02;
+
+pub struct PatternsPlugin;
+
+impl Plugin for PatternsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(RonAssetPlugin::<Pattern>::new(&["pattern.ron"]))
+            .add_systems(Update, (
+                sync_pattern_assets,
+                aim_emitters,
+                fire_emitters,
+            ).chain());
+    }
+}
+
+/// Describes a repeating volley of projectiles. Angles are in degrees so they're easy to author
+/// in RON files.
+#[derive(Clone, Debug, Deserialize, Asset, TypePath)]
+pub struct Pattern {
+    pub shape: PatternShape,
+    /// Seconds between volleys.
+    #[serde(default = "default_interval")]
+    pub interval: f32,
+    pub speed: f32,
+    #[serde(default = "default_damage")]
+    pub damage: f32,
+    /// How fast the whole pattern rotates, in degrees per second.
+    #[serde(default)]
+    pub angular_velocity: f32,
+    /// How much the pattern rotates after each volley, in degrees.
+    #[serde(default)]
+    pub spin: f32,
+    /// How far from the emitter projectiles are spawned.
+    #[serde(default)]
+    pub offset: f32,
+    /// Extra behaviours added to every projectile in the pattern.
+    #[serde(default)]
+    pub modifiers: ProjectileModifiers,
+}
+
+fn default_interval() -> f32 {
+    1.0
+}
+
+fn default_damage() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum PatternShape {
+    /// Projectiles spaced evenly around a full circle.
+    Ring { count: u32 },
+    /// A ring with only a few arms. Pair with angular_velocity or spin to get a spiral.
+    Spiral { arms: u32 },
+    /// Projectiles spaced evenly across an arc centered on the aim direction.
+    Fan { count: u32, spread: f32 },
+    /// A burst fired straight at the aim direction with some random spread. Ignores rotation.
+    Aimed { count: u32, spread: f32 },
+    /// A single projectile per volley. Pair with angular_velocity to sweep it around.
+    Stream,
+}
+
+impl Pattern {
+    /// Returns the direction of each projectile in a single volley.
+    pub fn volley(&self, rotation: f32, aim: Vec2) -> Vec<Vec2> {
+        let aim_angle = if aim == Vec2::ZERO {
+            FRAC_PI_2
+        } else {
+            aim.y.atan2(aim.x)
+        };
+        let base = aim_angle + rotation;
+
+        match self.shape {
+            PatternShape::Ring { count } | PatternShape::Spiral { arms: count } => {
+                let step = TAU / count.max(1) as f32;
+                (0..count)
+                    .map(|i| Vec2::from_angle(base + step * i as f32))
+                    .collect()
+            },
+            PatternShape::Fan { count, spread } => {
+                if count <= 1 {
+                    return vec![Vec2::from_angle(base); count as usize];
+                }
+                let spread = spread.to_radians();
+                let step = spread / (count - 1) as f32;
+                (0..count)
+                    .map(|i| Vec2::from_angle(base - spread / 2.0 + step * i as f32))
+                    .collect()
+            },
+            PatternShape::Aimed { count, spread } => {
+                let spread = spread.to_radians();
+                (0..count)
+                    .map(|_| Vec2::from_angle(aim_angle + (fastrand::f32() - 0.5) * spread))
+                    .collect()
+            },
+            PatternShape::Stream => vec![Vec2::from_angle(base)],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub enum EmitterAim {
+    /// Always aim in the given direction.
+    Fixed(Vec2),
+    /// Aim at the closest entity with health from another faction.
+    #[default]
+    NearestOpponent,
+    /// Aim and fire using the PlayerInput on this entity or one of its ancestors.
+    PlayerInput,
+}
+
+#[derive(Component)]
+pub struct PatternEmitter {
+    /// The pattern to fire. Emitters loading their pattern from a file won't fire until it's
+    /// loaded.
+    pub pattern: Option<Pattern>,
+    pub aim: EmitterAim,
+    pub active: bool,
+    direction: Vec2,
+    rotation: f32,
+    cooldown: f32,
+}
+
+impl PatternEmitter {
+    pub fn empty(aim: EmitterAim) -> Self {
+        Self {
+            pattern: None,
+            aim,
+            active: true,
+            direction: Vec2::NEG_Y,
+            rotation: 0.0,
+            cooldown: 0.0,
+        }
+    }
+}
+
+/// Keeps a PatternEmitter's pattern in sync with a pattern asset, including hot reloads.
+#[derive(Component)]
+pub struct PatternHandle(pub Handle<Pattern>);
+
+#[derive(Bundle)]
+pub struct PatternEmitterBundle {
+    emitter: PatternEmitter,
+    handle: PatternHandle,
+}
+
+impl PatternEmitterBundle {
+    pub fn from_asset(handle: Handle<Pattern>, aim: EmitterAim) -> Self {
+        Self {
+            emitter: PatternEmitter::empty(aim),
+            handle: PatternHandle(handle),
+        }
+    }
+}
+
+fn sync_pattern_assets(
+    mut asset_events: EventReader<AssetEvent<Pattern>>,
+    patterns: Res<Assets<Pattern>>,
+    mut emitter_q: Query<(Ref<PatternHandle>, &mut PatternEmitter)>,
+) {
+    let changed: Vec<AssetId<Pattern>> = asset_events.read()
+        .filter_map(|event| match *event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => Some(id),
+            _ => None,
+        })
+        .collect();
+
+    for (handle, mut emitter) in emitter_q.iter_mut() {
+        if !handle.is_added() && !changed.contains(&handle.0.id()) {
+            continue;
+        }
+        if let Some(pattern) = patterns.get(&handle.0) {
+            emitter.pattern = Some(pattern.clone());
+        }
+    }
+}
+
+fn aim_emitters(
+    mut emitter_q: Query<(Entity, &mut PatternEmitter, &GlobalTransform, &Faction)>,
+    target_q: Query<(&GlobalTransform, &Faction), With<Health>>,
+    input_q: Query<&PlayerInput>,
+    parent_q: Query<&Parent>,
+) {
+    for (entity, mut emitter, transform, faction) in emitter_q.iter_mut() {
+        let pos = transform.translation().truncate();
+        match emitter.aim {
+            EmitterAim::Fixed(dir) => {
+                emitter.direction = dir.normalize_or_zero();
+            },
+            EmitterAim::NearestOpponent => {
+                let nearest = target_q.iter()
+                    .filter(|&(_, other_faction)| other_faction != faction)
+                    .map(|(other_transform, _)| other_transform.translation().truncate())
+                    .min_by(|a, b| a.distance_squared(pos).total_cmp(&b.distance_squared(pos)));
+                if let Some(target) = nearest {
+                    emitter.direction = (target - pos).normalize_or_zero();
+                }
+            },
+            EmitterAim::PlayerInput => {
+                let input = std::iter::once(entity)
+                    .chain(parent_q.iter_ancestors(entity))
+                    .find_map(|e| input_q.get(e).ok());
+                if let Some(input) = input {
+                    emitter.active = input.shoot;
+                    if input.aim != Vec2::ZERO {
+                        emitter.direction = input.aim;
+                    }
+                }
+            },
+        }
+    }
+}
+
+fn fire_emitters(
+    mut projectiles: Projectiles,
+    time: Res<Time>,
+    mut fire_events: EventWriter<FireEvent>,
+    mut emitter_q: Query<(Entity, &mut PatternEmitter, &GlobalTransform, &Faction)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut emitter, transform, faction) in emitter_q.iter_mut() {
+        let emitter = &mut *emitter;
+        let Some(pattern) = &emitter.pattern else {
+            continue;
+        };
+
+        emitter.rotation = (emitter.rotation + pattern.angular_velocity.to_radians() * dt) % TAU;
+        emitter.cooldown -= dt;
+        if !emitter.active {
+            emitter.cooldown = emitter.cooldown.max(0.0);
+            continue;
+        }
+        if emitter.cooldown > 0.0 {
+            continue;
+        }
+        emitter.cooldown = (emitter.cooldown + pattern.interval.max(MIN_INTERVAL)).max(0.0);
+
+        let origin = transform.translation().truncate();
+        for dir in pattern.volley(emitter.rotation, emitter.direction) {
+            let pos = origin + dir * pattern.offset;
+            let mut projectile = projectiles.spawn(pos, dir * pattern.speed, pattern.damage, *faction);
+            pattern.modifiers.insert_into(&mut projectile);
+        }
+        emitter.rotation = (emitter.rotation + pattern.spin.to_radians()) % TAU;
+        fire_events.send(FireEvent {
+            entity,
+            faction: *faction,
+            pos: origin,
+            dir: emitter.direction,
+        });
+    }
+}
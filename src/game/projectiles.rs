@@ -1,29 +1,186 @@
+use bevy::ecs::system::{EntityCommands, SystemParam};
 use bevy::prelude::*;
-use bevy_prototype_lyon::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use bevy::utils::HashSet;
+use bevy_rapier2d::prelude::ColliderDisabled;
+use serde::Deserialize;
 
 use crate::{
     game::{
-        combat::HitBoxBundle,
+        arena::{Arena, BoundsBehavior},
+        combat::{HitBoxBundle, HitSpec},
         factions::Faction,
+        health::Health,
     },
     physics::{groups, Velocity},
 };
 
+const PROJECTILE_RADIUS: f32 = 8.0;
+const PROJECTILE_LIFETIME: f32 = 5.0;
+
 pub struct ProjectilesPlugin;
 
 impl Plugin for ProjectilesPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, projectile_movement)
+            .init_resource::<ProjectileAssets>()
+            .init_resource::<ProjectilePool>()
+            .register_type::<Projectile>()
+            .register_type::<Homing>()
+            .register_type::<Pierce>()
+            .register_type::<Ricochet>()
+            .register_type::<Acceleration>()
+            .register_type::<SplitOnExpire>()
+            .add_systems(First, recycle_released_projectiles)
+            .add_systems(Update, (
+                projectile_homing,
+                projectile_acceleration,
+                projectile_movement,
+                projectile_ricochet,
+                projectile_bounds,
+            ).chain())
             .add_systems(PostUpdate, update_lifetimes);
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 pub struct Projectile {
     lifetime: Timer,
 }
 
+impl Default for Projectile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Projectile {
+    fn new() -> Self {
+        Self {
+            lifetime: Timer::from_seconds(PROJECTILE_LIFETIME, TimerMode::Once),
+        }
+    }
+}
+
+/// Marks a projectile entity that's sitting in the pool waiting to be reused.
+#[derive(Component)]
+pub struct Pooled;
+
+/// Mesh and materials shared by every projectile.
+#[derive(Resource)]
+pub struct ProjectileAssets {
+    mesh: Mesh2dHandle,
+    player_material: Handle<ColorMaterial>,
+    enemy_material: Handle<ColorMaterial>,
+}
+
+impl ProjectileAssets {
+    fn material(&self, faction: Faction) -> Handle<ColorMaterial> {
+        match faction {
+            Faction::Player => self.player_material.clone(),
+            Faction::Enemy => self.enemy_material.clone(),
+        }
+    }
+}
+
+impl FromWorld for ProjectileAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world.resource_mut::<Assets<Mesh>>()
+            .add(shape::Circle::new(PROJECTILE_RADIUS).into());
+        let mut materials = world.resource_mut::<Assets<ColorMaterial>>();
+        Self {
+            mesh: mesh.into(),
+            player_material: materials.add(ColorMaterial::from(Color::CYAN * 4.0)),
+            enemy_material: materials.add(ColorMaterial::from(Color::RED * 4.0)),
+        }
+    }
+}
+
+/// Keeps track of projectile entities that can be reused instead of spawning new ones.
+#[derive(Default, Resource)]
+pub struct ProjectilePool {
+    free: Vec<Entity>,
+    // Released this frame. Only made available next frame so a reuse can't be applied before the
+    // release.
+    released: HashSet<Entity>,
+    active: usize,
+    spawned: usize,
+    reused: usize,
+}
+
+impl ProjectilePool {
+    /// Projectiles currently in flight.
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Projectiles waiting in the pool.
+    pub fn pooled(&self) -> usize {
+        self.free.len() + self.released.len()
+    }
+
+    /// Total projectile entities ever spawned.
+    pub fn spawned(&self) -> usize {
+        self.spawned
+    }
+
+    /// Total times a pooled projectile was reused.
+    pub fn reused(&self) -> usize {
+        self.reused
+    }
+}
+
+/// Spawns projectiles out of the pool and returns them to it.
+#[derive(SystemParam)]
+pub struct Projectiles<'w, 's> {
+    commands: Commands<'w, 's>,
+    pool: ResMut<'w, ProjectilePool>,
+    assets: Res<'w, ProjectileAssets>,
+}
+
+impl<'w, 's> Projectiles<'w, 's> {
+    pub fn spawn(&mut self, pos: Vec2, vel: Vec2, damage: f32, faction: Faction) -> EntityCommands<'w, 's, '_> {
+        self.pool.active += 1;
+        if let Some(entity) = self.pool.free.pop() {
+            self.pool.reused += 1;
+            let transform = Transform::from_translation(pos.extend(0.0));
+            let material = self.assets.material(faction);
+            let mut entity = self.commands.entity(entity);
+            entity
+                .remove::<(Pooled, ColliderDisabled)>()
+                .insert((
+                    Projectile::new(),
+                    faction,
+                    Velocity::new(vel),
+                    HitSpec { damage },
+                    transform,
+                    // Also update the global transform so physics doesn't see the old position.
+                    GlobalTransform::from(transform),
+                    Visibility::Inherited,
+                    material,
+                ));
+            entity
+        } else {
+            self.pool.spawned += 1;
+            let bundle = ProjectileBundle::new(pos, vel, damage, faction, &self.assets);
+            self.commands.spawn(bundle)
+        }
+    }
+
+    /// Hides the projectile and disables its collider so it can be reused later.
+    pub fn release(&mut self, entity: Entity) {
+        if !self.pool.released.insert(entity) {
+            // Already released this frame.
+            return;
+        }
+        self.pool.active = self.pool.active.saturating_sub(1);
+        self.commands.entity(entity)
+            .remove::<(Projectile, Homing, Pierce, Ricochet, Acceleration, SplitOnExpire, BoundsBehavior)>()
+            .insert((Pooled, ColliderDisabled, Visibility::Hidden));
+    }
+}
+
 #[derive(Bundle)]
 pub struct ProjectileBundle {
     name: Name,
@@ -31,40 +188,158 @@ pub struct ProjectileBundle {
     faction: Faction,
     velocity: Velocity,
     hit_box: HitBoxBundle,
-    shape: ShapeBundle,
-    fill: Fill,
+    mesh: ColorMesh2dBundle,
 }
 
 impl ProjectileBundle {
-    pub fn new(pos: Vec2, vel: Vec2, damage: f32, faction: Faction) -> Self {
-        let radius = 8.0;
-        let shape = shapes::Circle {
-            radius,
-            ..default()
-        };
+    fn new(pos: Vec2, vel: Vec2, damage: f32, faction: Faction, assets: &ProjectileAssets) -> Self {
         let transform = Transform::from_translation(pos.extend(0.0));
-        let color = match faction {
-            Faction::Player => Color::CYAN * 4.0,
-            Faction::Enemy => Color::RED * 4.0,
-        };
         Self {
             name: Name::new("Projectile"),
-            projectile: Projectile {
-                lifetime: Timer::from_seconds(5.0, TimerMode::Once)
-            },
+            projectile: Projectile::new(),
             faction,
             velocity: Velocity::new(vel),
-            hit_box: HitBoxBundle::circle(radius, damage, groups::NONE),
-            shape: ShapeBundle {
-                path: GeometryBuilder::build_as(&shape),
-                spatial: SpatialBundle::from_transform(transform),
+            hit_box: HitBoxBundle::circle(PROJECTILE_RADIUS, damage, groups::NONE),
+            mesh: ColorMesh2dBundle {
+                mesh: assets.mesh.clone(),
+                material: assets.material(faction),
+                transform,
                 ..default()
             },
-            fill: Fill::color(color),
         }
     }
 }
 
+/// Steers the projectile toward the nearest entity with health from another faction.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Component, Reflect)]
+#[reflect(Component)]
+pub struct Homing {
+    /// Max turn speed in degrees per second.
+    pub turn_rate: f32,
+    /// Targets further away than this are ignored.
+    pub range: f32,
+}
+
+/// Lets the projectile pass through this many targets before it's used up.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Component, Reflect)]
+#[reflect(Component)]
+pub struct Pierce {
+    pub remaining: u32,
+}
+
+/// Bounces the projectile off the arena bounds this many times.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Component, Reflect)]
+#[reflect(Component)]
+pub struct Ricochet {
+    pub remaining: u32,
+}
+
+/// Changes the projectile's speed over time. Drag is the fraction of speed lost per second.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Component, Reflect)]
+#[reflect(Component)]
+pub struct Acceleration {
+    #[serde(default)]
+    pub accel: f32,
+    #[serde(default)]
+    pub drag: f32,
+}
+
+/// Spawns a ring of projectiles when this projectile's lifetime runs out.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Component, Reflect)]
+#[reflect(Component)]
+pub struct SplitOnExpire {
+    pub count: u32,
+    pub speed: f32,
+    pub damage: f32,
+}
+
+/// A set of optional modifiers that can be added to any projectile, e.g. from a pattern file.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ProjectileModifiers {
+    #[serde(default)]
+    pub homing: Option<Homing>,
+    #[serde(default)]
+    pub pierce: Option<Pierce>,
+    #[serde(default)]
+    pub ricochet: Option<Ricochet>,
+    #[serde(default)]
+    pub acceleration: Option<Acceleration>,
+    #[serde(default)]
+    pub split_on_expire: Option<SplitOnExpire>,
+    #[serde(default)]
+    pub bounds: Option<BoundsBehavior>,
+}
+
+impl ProjectileModifiers {
+    pub fn insert_into(&self, entity: &mut EntityCommands) {
+        if let Some(homing) = self.homing {
+            entity.insert(homing);
+        }
+        if let Some(pierce) = self.pierce {
+            entity.insert(pierce);
+        }
+        if let Some(ricochet) = self.ricochet {
+            entity.insert(ricochet);
+        }
+        if let Some(acceleration) = self.acceleration {
+            entity.insert(acceleration);
+        }
+        if let Some(split) = self.split_on_expire {
+            entity.insert(split);
+        }
+        if let Some(bounds) = self.bounds {
+            entity.insert(bounds);
+        }
+    }
+}
+
+fn projectile_homing(
+    time: Res<Time>,
+    mut projectile_q: Query<(&mut Velocity, &Homing, &GlobalTransform, &Faction), With<Projectile>>,
+    target_q: Query<(&GlobalTransform, &Faction), With<Health>>,
+) {
+    let dt = time.delta_seconds();
+    for (mut velocity, homing, transform, faction) in projectile_q.iter_mut() {
+        if velocity.inner == Vec2::ZERO {
+            continue;
+        }
+
+        let pos = transform.translation().truncate();
+        let range_squared = homing.range * homing.range;
+        let nearest = target_q.iter()
+            .filter(|&(_, other_faction)| other_faction != faction)
+            .map(|(other_transform, _)| other_transform.translation().truncate())
+            .filter(|other_pos| other_pos.distance_squared(pos) <= range_squared)
+            .min_by(|a, b| a.distance_squared(pos).total_cmp(&b.distance_squared(pos)));
+        let Some(target) = nearest else {
+            continue;
+        };
+
+        let desired = target - pos;
+        if desired == Vec2::ZERO {
+            continue;
+        }
+        let max_turn = homing.turn_rate.to_radians() * dt;
+        let turn = velocity.inner.angle_between(desired).clamp(-max_turn, max_turn);
+        velocity.inner = Vec2::from_angle(turn).rotate(velocity.inner);
+    }
+}
+
+fn projectile_acceleration(
+    time: Res<Time>,
+    mut projectile_q: Query<(&mut Velocity, &Acceleration), With<Projectile>>,
+) {
+    let dt = time.delta_seconds();
+    for (mut velocity, acceleration) in projectile_q.iter_mut() {
+        let speed = velocity.inner.length();
+        if speed == 0.0 {
+            continue;
+        }
+        let new_speed = ((speed + acceleration.accel * dt) * (1.0 - acceleration.drag * dt)).max(0.0);
+        velocity.inner *= new_speed / speed;
+    }
+}
+
 pub fn projectile_movement(
     time: Res<Time>,
     mut projectile_q: Query<(&mut Transform, &Velocity), With<Projectile>>,
@@ -75,15 +350,64 @@ pub fn projectile_movement(
     }
 }
 
+fn projectile_ricochet(
+    arena: Res<Arena>,
+    mut projectile_q: Query<(&mut Transform, &mut Velocity, &mut Ricochet), With<Projectile>>,
+) {
+    for (mut transform, mut velocity, mut ricochet) in projectile_q.iter_mut() {
+        if ricochet.remaining > 0 && arena.bounce(&mut transform, &mut velocity) {
+            ricochet.remaining -= 1;
+        }
+    }
+}
+
+fn projectile_bounds(
+    mut projectiles: Projectiles,
+    arena: Res<Arena>,
+    mut projectile_q: Query<(Entity, &mut Transform, &mut Velocity, Option<&BoundsBehavior>), With<Projectile>>,
+) {
+    for (entity, mut transform, mut velocity, behavior) in projectile_q.iter_mut() {
+        match behavior.copied().unwrap_or_default() {
+            BoundsBehavior::Despawn => {
+                if arena.should_cull(transform.translation.truncate()) {
+                    projectiles.release(entity);
+                }
+            },
+            BoundsBehavior::Wrap => {
+                arena.wrap(&mut transform);
+            },
+            BoundsBehavior::Bounce => {
+                arena.bounce(&mut transform, &mut velocity);
+            },
+        }
+    }
+}
+
+fn recycle_released_projectiles(
+    mut pool: ResMut<ProjectilePool>,
+) {
+    let pool = &mut *pool;
+    pool.free.extend(pool.released.drain());
+}
+
 pub fn update_lifetimes(
-    mut commands: Commands,
+    mut projectiles: Projectiles,
     time: Res<Time>,
-    mut projectile_q: Query<(Entity, &mut Projectile)>,
+    mut projectile_q: Query<(Entity, &mut Projectile, &Transform, &Faction, Option<&SplitOnExpire>)>,
 ) {
     let dt = time.delta();
-    for (entity, mut projectile) in projectile_q.iter_mut() {
+    for (entity, mut projectile, transform, faction, split) in projectile_q.iter_mut() {
         if projectile.lifetime.tick(dt).finished() {
-            commands.entity(entity).despawn_recursive();
+            projectiles.release(entity);
+
+            if let Some(split) = split {
+                let pos = transform.translation.truncate();
+                let step = std::f32::consts::TAU / split.count.max(1) as f32;
+                for i in 0..split.count {
+                    let vel = Vec2::from_angle(step * i as f32) * split.speed;
+                    projectiles.spawn(pos, vel, split.damage, *faction);
+                }
+            }
         }
     }
 }
@@ -0,0 +1,250 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Align2, Color32, Grid, RichText},
+    EguiContexts,
+};
+
+use crate::game::{
+    self,
+    input::PlayerInput,
+    meta::MetaProgress,
+    patterns::{EmitterAim, PatternEmitterBundle},
+    projectiles::{Projectile, Projectiles},
+    score::{HighScores, Score},
+    units::{self, SwarmParent},
+    waves::{WaveEntity, WavesManager},
+};
+
+// Ignore restart input for a moment so a held fire button doesn't skip the game over screen.
+const RESTART_DELAY: f32 = 1.0;
+/// Swarm size before any meta-progression upgrades.
+pub const STARTING_SWARM_SIZE: u32 = 20;
+
+pub struct RunPlugin;
+
+impl Plugin for RunPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<RunEndedEvent>()
+            .add_event::<RestartRunEvent>()
+            .init_resource::<Run>()
+            .add_systems(Update, (
+                tick_run,
+                detect_game_over,
+            ).chain());
+        if !game::is_headless(app) {
+            app.add_systems(Update, (
+                game_over_screen.run_if(run_over),
+                restart_run.run_if(on_event::<RestartRunEvent>()),
+            ).chain().after(detect_game_over));
+        }
+    }
+}
+
+/// The current attempt, from spawning the swarm until the last shooter dies.
+#[derive(Default, Resource)]
+pub struct Run {
+    elapsed: f32,
+    over: bool,
+    // Seconds since the run ended.
+    over_time: f32,
+}
+
+impl Run {
+    /// How long the run has lasted, in seconds.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.over
+    }
+
+    /// Picks up a saved run that had lasted this many seconds.
+    pub fn resume(elapsed: f32) -> Self {
+        Self {
+            elapsed,
+            ..default()
+        }
+    }
+}
+
+/// Sent once when the whole swarm has been destroyed.
+#[derive(Event)]
+pub struct RunEndedEvent;
+
+/// Sent to throw away the current run and start a fresh one.
+#[derive(Event)]
+pub struct RestartRunEvent;
+
+pub fn run_over(
+    run: Res<Run>,
+) -> bool {
+    run.over
+}
+
+/// Spawns a fresh swarm with the unit kind, weapon and size picked in the upgrade shop.
+pub fn start_run(
+    commands: &mut Commands,
+    swarm_q: &Query<Entity, With<SwarmParent>>,
+    meta: &MetaProgress,
+    asset_server: &AssetServer,
+) {
+    let swarm = units::spawn_swarm(commands, swarm_q, meta.starting_swarm_size(), meta.selected_unit);
+    if let Some(weapon) = meta.selected_weapon {
+        commands.entity(swarm).insert(PatternEmitterBundle::from_asset(
+            asset_server.load(weapon.pattern_path()),
+            EmitterAim::PlayerInput,
+        ));
+    }
+}
+
+/// Clears out the current run so a new one can take its place.
+#[derive(SystemParam)]
+pub struct RunReset<'w, 's> {
+    pub commands: Commands<'w, 's>,
+    projectiles: Projectiles<'w, 's>,
+    run: ResMut<'w, Run>,
+    score: ResMut<'w, Score>,
+    waves: ResMut<'w, WavesManager>,
+    wave_entity_q: Query<'w, 's, Entity, With<WaveEntity>>,
+    projectile_q: Query<'w, 's, Entity, With<Projectile>>,
+}
+
+impl<'w, 's> RunReset<'w, 's> {
+    /// Despawns the current wave, releases every projectile in flight and replaces the run's
+    /// progress.
+    pub fn reset(&mut self, run: Run, score: Score, waves: WavesManager) {
+        for entity in self.wave_entity_q.iter() {
+            self.commands.entity(entity).despawn_recursive();
+        }
+        for entity in self.projectile_q.iter() {
+            self.projectiles.release(entity);
+        }
+        *self.run = run;
+        *self.score = score;
+        *self.waves = waves;
+    }
+}
+
+fn tick_run(
+    time: Res<Time>,
+    mut run: ResMut<Run>,
+) {
+    if run.over {
+        run.over_time += time.delta_seconds();
+    } else {
+        run.elapsed += time.delta_seconds();
+    }
+}
+
+fn detect_game_over(
+    mut run: ResMut<Run>,
+    mut run_ended_events: EventWriter<RunEndedEvent>,
+    swarm_q: Query<Option<&Children>, With<SwarmParent>>,
+) {
+    if run.over {
+        return;
+    }
+    let Ok(children) = swarm_q.get_single() else {
+        return;
+    };
+    if children.map_or(true, |children| children.is_empty()) {
+        run.over = true;
+        run_ended_events.send(RunEndedEvent);
+    }
+}
+
+fn format_duration(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn game_over_screen(
+    mut egui_ctx: EguiContexts,
+    run: Res<Run>,
+    score: Res<Score>,
+    waves: Res<WavesManager>,
+    high_scores: Res<HighScores>,
+    mut restart_events: EventWriter<RestartRunEvent>,
+    input_q: Query<&PlayerInput, With<SwarmParent>>,
+) {
+    let ctx = egui_ctx.ctx_mut();
+    let mut restart = false;
+
+    egui::Window::new("Game Over")
+        .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            Grid::new("run_details")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Score");
+                    ui.label(RichText::new(score.points().to_string()).strong());
+                    ui.end_row();
+
+                    ui.label("Wave");
+                    ui.label(waves.wave().to_string());
+                    ui.end_row();
+
+                    ui.label("Kills");
+                    ui.label(score.kills().to_string());
+                    ui.end_row();
+
+                    ui.label("Best Combo");
+                    ui.label(format!("x{:.1}", score.best_multiplier()));
+                    ui.end_row();
+
+                    ui.label("Time");
+                    ui.label(format_duration(run.elapsed()));
+                    ui.end_row();
+                });
+
+            ui.separator();
+            ui.heading("High Scores");
+            Grid::new("high_scores")
+                .num_columns(5)
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in ["#", "Score", "Wave", "Kills", "Time"] {
+                        ui.label(RichText::new(header).strong());
+                    }
+                    ui.end_row();
+
+                    for (i, entry) in high_scores.entries.iter().enumerate() {
+                        let color = if high_scores.last_rank == Some(i) {
+                            Color32::YELLOW
+                        } else {
+                            Color32::WHITE
+                        };
+                        ui.label(RichText::new(format!("{}", i + 1)).color(color));
+                        ui.label(RichText::new(entry.points.to_string()).color(color));
+                        ui.label(RichText::new(entry.wave.to_string()).color(color));
+                        ui.label(RichText::new(entry.kills.to_string()).color(color));
+                        ui.label(RichText::new(format_duration(entry.duration)).color(color));
+                        ui.end_row();
+                    }
+                });
+
+            ui.separator();
+            let can_restart = run.over_time >= RESTART_DELAY;
+            restart |= ui.add_enabled(can_restart, egui::Button::new("Play Again")).clicked();
+            restart |= can_restart && input_q.iter().any(|input| input.reset_game);
+        });
+
+    if restart {
+        restart_events.send(RestartRunEvent);
+    }
+}
+
+fn restart_run(
+    mut reset: RunReset,
+    asset_server: Res<AssetServer>,
+    meta: Res<MetaProgress>,
+    swarm_q: Query<Entity, With<SwarmParent>>,
+) {
+    reset.reset(default(), default(), default());
+    start_run(&mut reset.commands, &swarm_q, &meta, &asset_server);
+}
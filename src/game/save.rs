@@ -0,0 +1,397 @@
+use std::path::PathBuf;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Align2},
+    EguiContexts,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{
+        health::Health,
+        patterns::{EmitterAim, PatternEmitterBundle, PatternHandle},
+        run::{Run, RunEndedEvent, RunReset},
+        score::Score,
+        units::{self, BasicShooter, BasicShooterBundle, SwarmParent, UnitKind},
+        waves::WavesManager,
+    },
+    persist::{self, PersistError},
+};
+
+const RUN_SAVE_FILENAME: &str = "run.ron";
+// Bump this and handle the old version in migrate() when changing RunSave's format.
+const RUN_SAVE_VERSION: u32 = 1;
+const QUICK_SAVE_KEY: KeyCode = KeyCode::F5;
+const QUICK_LOAD_KEY: KeyCode = KeyCode::F9;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<ResumeRunEvent>()
+            .init_resource::<RunSavePath>()
+            .add_systems(Startup, offer_resume)
+            .add_systems(Update, (
+                continue_prompt.run_if(resource_exists::<ContinuePrompt>()),
+                save_run.run_if(quick_save_pressed),
+                load_run.run_if(resume_requested),
+                discard_run_save,
+            ).chain());
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            app.add_systems(Last, save_run.run_if(on_event::<AppExit>()));
+        }
+    }
+}
+
+/// Sent to replace the current run with the saved one.
+#[derive(Event)]
+pub struct ResumeRunEvent;
+
+/// Where the unfinished run is saved.
+#[derive(Resource)]
+pub struct RunSavePath(pub PathBuf);
+
+impl Default for RunSavePath {
+    fn default() -> Self {
+        Self(persist::config_path(RUN_SAVE_FILENAME))
+    }
+}
+
+/// Asks whether to pick up the run left unfinished last time.
+#[derive(Resource)]
+struct ContinuePrompt;
+
+/// Everything needed to pick a run back up. Enemies aren't saved: a wave in progress starts over.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RunSave {
+    pub version: u32,
+    /// The global RNG's seed when the run was saved.
+    pub rng_seed: u64,
+    /// Seconds the run had lasted.
+    pub elapsed: f32,
+    pub completed_waves: u32,
+    /// Whether an upgrade was waiting to be picked.
+    pub intermission: bool,
+    pub score: Score,
+    pub swarm: SwarmSave,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SwarmSave {
+    pub params: SwarmParent,
+    pub position: Vec2,
+    /// Asset path of the swarm's weapon pattern, if it has one.
+    pub pattern: Option<String>,
+    pub shooters: Vec<ShooterSave>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ShooterSave {
+    pub kind: UnitKind,
+    /// Relative to the swarm.
+    pub position: Vec2,
+    pub health: f32,
+    pub max_health: f32,
+}
+
+impl RunSave {
+    fn migrate(self) -> Result<Self, PersistError> {
+        if self.version > RUN_SAVE_VERSION {
+            return Err(PersistError::UnsupportedVersion(self.version));
+        }
+        Ok(Self {
+            version: RUN_SAVE_VERSION,
+            ..self
+        })
+    }
+}
+
+fn quick_save_pressed(
+    keys: Res<Input<KeyCode>>,
+) -> bool {
+    keys.just_pressed(QUICK_SAVE_KEY)
+}
+
+fn resume_requested(
+    keys: Res<Input<KeyCode>>,
+    mut resume_events: EventReader<ResumeRunEvent>,
+) -> bool {
+    // Always read the events so they aren't handled twice.
+    resume_events.read().count() > 0 || keys.just_pressed(QUICK_LOAD_KEY)
+}
+
+fn offer_resume(
+    mut commands: Commands,
+    path: Res<RunSavePath>,
+) {
+    if !cfg!(target_arch = "wasm32") && path.0.exists() {
+        commands.insert_resource(ContinuePrompt);
+    }
+}
+
+fn continue_prompt(
+    mut commands: Commands,
+    mut egui_ctx: EguiContexts,
+    mut resume_events: EventWriter<ResumeRunEvent>,
+) {
+    let mut answered = false;
+    egui::Window::new("Continue?")
+        .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.label("Pick up the run you left unfinished?");
+            ui.horizontal(|ui| {
+                if ui.button("Continue").clicked() {
+                    resume_events.send(ResumeRunEvent);
+                    answered = true;
+                }
+                answered |= ui.button("New Run").clicked();
+            });
+        });
+    if answered {
+        commands.remove_resource::<ContinuePrompt>();
+    }
+}
+
+fn save_run(
+    path: Res<RunSavePath>,
+    asset_server: Res<AssetServer>,
+    run: Res<Run>,
+    score: Res<Score>,
+    waves: Res<WavesManager>,
+    swarm_q: Query<(&SwarmParent, &Transform, Option<&Children>, Option<&PatternHandle>)>,
+    shooter_q: Query<(&BasicShooter, &Health, &Transform)>,
+) {
+    // Finished runs can't be resumed.
+    if cfg!(target_arch = "wasm32") || run.is_over() {
+        return;
+    }
+    let Ok((params, transform, children, pattern)) = swarm_q.get_single() else {
+        return;
+    };
+
+    let shooters = children.into_iter()
+        .flat_map(|children| children.iter())
+        .filter_map(|&child| shooter_q.get(child).ok())
+        .map(|(shooter, health, transform)| ShooterSave {
+            kind: shooter.kind(),
+            position: transform.translation.truncate(),
+            health: health.current(),
+            max_health: health.max(),
+        })
+        .collect();
+    let save = RunSave {
+        version: RUN_SAVE_VERSION,
+        rng_seed: fastrand::get_seed(),
+        elapsed: run.elapsed(),
+        completed_waves: waves.completed_waves(),
+        intermission: waves.in_intermission(),
+        score: score.clone(),
+        swarm: SwarmSave {
+            params: params.clone(),
+            position: transform.translation.truncate(),
+            pattern: pattern
+                .and_then(|pattern| asset_server.get_path(pattern.0.id()))
+                .map(|path| path.to_string()),
+            shooters,
+        },
+    };
+
+    let path = &path.0;
+    match persist::save_ron(path, &save) {
+        Ok(()) => info!("Saved run to {}", path.display()),
+        Err(e) => error!("Could not save run: {}", e),
+    }
+}
+
+fn load_run(
+    mut reset: RunReset,
+    path: Res<RunSavePath>,
+    asset_server: Res<AssetServer>,
+    swarm_q: Query<Entity, With<SwarmParent>>,
+) {
+    if cfg!(target_arch = "wasm32") {
+        return;
+    }
+    // Loading with the quick load key answers the prompt too.
+    reset.commands.remove_resource::<ContinuePrompt>();
+
+    let path = &path.0;
+    let save = match persist::load_ron::<RunSave>(path).and_then(|save| save.map(RunSave::migrate).transpose()) {
+        Ok(Some(save)) => save,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Could not load run from {}: {}", path.display(), e);
+            return;
+        },
+    };
+
+    fastrand::seed(save.rng_seed);
+    reset.reset(
+        Run::resume(save.elapsed),
+        save.score,
+        WavesManager::resume(save.completed_waves, save.intermission),
+    );
+
+    let commands = &mut reset.commands;
+    let swarm = save.swarm;
+    let entity = units::spawn_swarm(commands, &swarm_q, 0, swarm.params.unit_kind);
+    commands.entity(entity)
+        .insert((
+            SwarmParent {
+                last_fired_time: -1.0,
+                ..swarm.params
+            },
+            Transform::from_translation(swarm.position.extend(0.0)),
+        ))
+        .with_children(|b| {
+            for shooter in swarm.shooters {
+                let health = Health::new(shooter.max_health).with_current(shooter.health);
+                b.spawn(BasicShooterBundle::new(shooter.position, shooter.kind).with_health(health));
+            }
+        });
+    if let Some(pattern) = swarm.pattern {
+        commands.entity(entity).insert(PatternEmitterBundle::from_asset(
+            asset_server.load(pattern),
+            EmitterAim::PlayerInput,
+        ));
+    }
+    info!("Resumed run from {}", path.display());
+}
+
+/// A run that's over shouldn't be resumed next time.
+fn discard_run_save(
+    path: Res<RunSavePath>,
+    mut run_ended_events: EventReader<RunEndedEvent>,
+) {
+    if cfg!(target_arch = "wasm32") || run_ended_events.read().count() == 0 {
+        return;
+    }
+    if let Err(e) = persist::remove(&path.0) {
+        error!("Could not delete run save: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        arena::Arena,
+        headless,
+        projectiles::ProjectilesPlugin,
+    };
+
+    fn test_app(path: PathBuf) -> App {
+        let mut app = headless::minimal_app();
+        app
+            .add_event::<RunEndedEvent>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Arena>()
+            .init_resource::<Run>()
+            .init_resource::<Score>()
+            .init_resource::<WavesManager>()
+            .insert_resource(RunSavePath(path))
+            .add_plugins((
+                ProjectilesPlugin,
+                SavePlugin,
+            ));
+        app
+    }
+
+    fn run_save() -> RunSave {
+        let mut score = Score::default();
+        score.add_kill(250);
+        RunSave {
+            version: RUN_SAVE_VERSION,
+            rng_seed: 42,
+            elapsed: 93.5,
+            completed_waves: 4,
+            intermission: true,
+            score,
+            swarm: SwarmSave {
+                params: SwarmParent {
+                    damage_bonus: 2.0,
+                    unit_kind: UnitKind::Tank,
+                    ..default()
+                },
+                position: Vec2::new(120.0, -40.0),
+                pattern: None,
+                shooters: vec![
+                    ShooterSave {
+                        kind: UnitKind::Tank,
+                        position: Vec2::new(10.0, 20.0),
+                        health: 3.0,
+                        max_health: 8.0,
+                    },
+                    ShooterSave {
+                        kind: UnitKind::Gunner,
+                        position: Vec2::new(-30.0, 5.0),
+                        health: 2.0,
+                        max_health: 2.0,
+                    },
+                ],
+            },
+        }
+    }
+
+    #[test]
+    fn run_save_round_trips_through_load_run() {
+        let dir = std::env::temp_dir().join(format!("run-save-test-{}", std::process::id()));
+        let path = dir.join(RUN_SAVE_FILENAME);
+        let mut app = test_app(path.clone());
+        app.update();
+
+        let save = run_save();
+        persist::save_ron(&path, &save).unwrap();
+        app.world.send_event(ResumeRunEvent);
+        app.update();
+
+        assert_eq!(app.world.resource::<Run>().elapsed(), save.elapsed);
+        assert_eq!(app.world.resource::<Score>().points(), save.score.points());
+        let waves = app.world.resource::<WavesManager>();
+        assert_eq!(waves.completed_waves(), save.completed_waves);
+        assert_eq!(waves.in_intermission(), save.intermission);
+
+        // Saving the loaded run should give back what was loaded.
+        persist::remove(&path).unwrap();
+        app.world.resource_mut::<Input<KeyCode>>().press(QUICK_SAVE_KEY);
+        app.update();
+        let resaved = persist::load_ron::<RunSave>(&path).unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(resaved.elapsed, save.elapsed);
+        assert_eq!(resaved.completed_waves, save.completed_waves);
+        assert_eq!(resaved.intermission, save.intermission);
+        assert_eq!(resaved.score.points(), save.score.points());
+        assert_eq!(resaved.swarm.position, save.swarm.position);
+        assert_eq!(resaved.swarm.params.unit_kind, UnitKind::Tank);
+        assert_eq!(resaved.swarm.params.damage_bonus, 2.0);
+        let mut shooters: Vec<_> = resaved.swarm.shooters.iter()
+            .map(|shooter| (shooter.kind, shooter.position, shooter.health, shooter.max_health))
+            .collect();
+        shooters.sort_by(|a, b| a.1.x.total_cmp(&b.1.x));
+        assert_eq!(shooters, vec![
+            (UnitKind::Gunner, Vec2::new(-30.0, 5.0), 2.0, 2.0),
+            (UnitKind::Tank, Vec2::new(10.0, 20.0), 3.0, 8.0),
+        ]);
+    }
+
+    #[test]
+    fn unfinished_run_is_offered_instead_of_resumed() {
+        let dir = std::env::temp_dir().join(format!("run-save-prompt-test-{}", std::process::id()));
+        let path = dir.join(RUN_SAVE_FILENAME);
+        persist::save_ron(&path, &run_save()).unwrap();
+        let mut app = test_app(path);
+        app.world.run_schedule(Startup);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(app.world.contains_resource::<ContinuePrompt>());
+        assert_eq!(app.world.resource::<Run>().elapsed(), 0.0);
+        assert_eq!(app.world.resource::<WavesManager>().completed_waves(), 0);
+    }
+}
@@ -0,0 +1,177 @@
+use std::any::TypeId;
+use std::path::Path;
+use std::{fmt, fs};
+
+use bevy::ecs::system::SystemState;
+use bevy::prelude::*;
+use bevy::scene::{serde::SceneDeserializer, DynamicEntity, SceneSpawnError};
+use bevy::utils::HashMap;
+use serde::de::DeserializeSeed;
+
+use crate::{
+    game::{
+        ai::SimpleShooterAi,
+        enemies::{Boss, Enemy, EnemyBundle},
+        factions::Faction,
+        health::Health,
+        patterns::{EmitterAim, PatternEmitterBundle},
+        projectiles::{Projectile, Projectiles},
+        sprites::Archetype,
+        units::{BasicShooter, BasicShooterBundle, SwarmBundle, SwarmParent},
+        waves::{WaveEntity, WavesManager, BOSS_PATTERN},
+    },
+    persist::PersistError,
+    physics::{PlayerMovement, Velocity},
+};
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Persist(PersistError),
+    Spawn(SceneSpawnError),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Persist(e) => write!(f, "{}", e),
+            Self::Spawn(e) => write!(f, "could not spawn scene: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<PersistError> for ScenarioError {
+    fn from(e: PersistError) -> Self {
+        Self::Persist(e)
+    }
+}
+
+impl From<SceneSpawnError> for ScenarioError {
+    fn from(e: SceneSpawnError) -> Self {
+        Self::Spawn(e)
+    }
+}
+
+/// Captures the swarm, its shooters and every enemy as a scene. Only gameplay components are
+/// included: meshes, colliders and other visuals are rebuilt from them when loading.
+/// Projectiles in flight aren't included.
+pub fn dump_scenario(world: &World) -> DynamicScene {
+    let entities = world.iter_entities()
+        .filter(|entity| {
+            entity.contains::<SwarmParent>() || entity.contains::<BasicShooter>() || entity.contains::<Enemy>()
+        })
+        .map(|entity| entity.id());
+
+    DynamicSceneBuilder::from_world(world)
+        .deny_all()
+        .allow::<Name>()
+        .allow::<Transform>()
+        .allow::<Parent>()
+        .allow::<Children>()
+        .allow::<SwarmParent>()
+        .allow::<PlayerMovement>()
+        .allow::<BasicShooter>()
+        .allow::<Enemy>()
+        .allow::<Boss>()
+        .allow::<SimpleShooterAi>()
+        .allow::<WaveEntity>()
+        .allow::<Archetype>()
+        .allow::<Faction>()
+        .allow::<Health>()
+        .allow::<Velocity>()
+        .extract_entities(entities)
+        .build()
+}
+
+/// Writes the current scenario to a scene file.
+pub fn save_scenario(world: &World, path: &Path) -> Result<(), ScenarioError> {
+    let scene = dump_scenario(world);
+    let contents = scene.serialize_ron(&world.resource::<AppTypeRegistry>().0)
+        .map_err(PersistError::from)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(PersistError::from)?;
+    }
+    fs::write(path, contents).map_err(PersistError::from)?;
+    Ok(())
+}
+
+/// Replaces the swarm and enemies with the ones in a scene file. The loaded enemies become the
+/// current wave.
+pub fn load_scenario(world: &mut World, path: &Path) -> Result<(), ScenarioError> {
+    let contents = fs::read_to_string(path).map_err(PersistError::from)?;
+    let mut deserializer = ron::de::Deserializer::from_str(&contents).map_err(PersistError::from)?;
+    let scene = {
+        let type_registry = world.resource::<AppTypeRegistry>().read();
+        SceneDeserializer { type_registry: &type_registry }
+            .deserialize(&mut deserializer)
+            .map_err(|e| PersistError::from(deserializer.span_error(e)))?
+    };
+
+    clear_scenario(world);
+
+    // Spawn each entity with everything it needs to be drawn and collide, then let the scene
+    // overwrite its gameplay components.
+    let mut entity_map = HashMap::default();
+    for dynamic_entity in scene.entities.iter() {
+        if let Some(entity) = spawn_base(world, dynamic_entity) {
+            entity_map.insert(dynamic_entity.entity, entity);
+        }
+    }
+    scene.write_to_world(world, &mut entity_map)?;
+
+    let enemies = world.query_filtered::<Entity, With<WaveEntity>>()
+        .iter(world)
+        .collect();
+    world.resource_mut::<WavesManager>().replace_wave(enemies);
+    Ok(())
+}
+
+fn clear_scenario(world: &mut World) {
+    let entities: Vec<Entity> = world.query_filtered::<Entity, Or<(With<SwarmParent>, With<Enemy>, With<WaveEntity>)>>()
+        .iter(world)
+        .collect();
+    for entity in entities {
+        // Shooters may already be gone along with their swarm.
+        if world.get_entity(entity).is_some() {
+            world.entity_mut(entity).despawn_recursive();
+        }
+    }
+
+    let mut state = SystemState::<(Projectiles, Query<Entity, With<Projectile>>)>::new(world);
+    let (mut projectiles, projectile_q) = state.get_mut(world);
+    for entity in projectile_q.iter() {
+        projectiles.release(entity);
+    }
+    state.apply(world);
+}
+
+fn find_component<T: FromReflect>(dynamic_entity: &DynamicEntity) -> Option<T> {
+    dynamic_entity.components.iter()
+        .find(|component| {
+            component.get_represented_type_info().map(|info| info.type_id()) == Some(TypeId::of::<T>())
+        })
+        .and_then(|component| T::from_reflect(component.as_ref()))
+}
+
+fn spawn_base(world: &mut World, dynamic_entity: &DynamicEntity) -> Option<Entity> {
+    if let Some(swarm) = find_component::<SwarmParent>(dynamic_entity) {
+        return Some(world.spawn(SwarmBundle::new(swarm.unit_kind)).id());
+    }
+    if let Some(shooter) = find_component::<BasicShooter>(dynamic_entity) {
+        return Some(world.spawn(BasicShooterBundle::new(Vec2::ZERO, shooter.kind())).id());
+    }
+    match find_component::<Archetype>(dynamic_entity)? {
+        Archetype::Enemy => Some(world.spawn(EnemyBundle::new(Vec2::ZERO)).id()),
+        Archetype::Boss => {
+            let pattern = world.resource::<AssetServer>().load(BOSS_PATTERN);
+            let entity = world.spawn((
+                EnemyBundle::boss(Vec2::ZERO),
+                PatternEmitterBundle::from_asset(pattern, EmitterAim::NearestOpponent),
+            )).id();
+            Some(entity)
+        },
+        // Shooters are handled above.
+        Archetype::BasicShooter => None,
+    }
+}
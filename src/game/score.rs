@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{
+        self,
+        combat::DeathEvent,
+        factions::Faction,
+        run::{Run, RunEndedEvent},
+        sprites::Archetype,
+        waves::WavesManager,
+    },
+    persist::{self, PersistError},
+};
+
+// Each kill adds this much to the combo multiplier.
+const COMBO_STEP: f32 = 0.1;
+const MAX_MULTIPLIER: f32 = 8.0;
+// Seconds without a kill before the multiplier starts to decay.
+const COMBO_TIMEOUT: f32 = 2.0;
+// Multiplier lost per second once it's decaying.
+const COMBO_DECAY: f32 = 0.5;
+const HIGH_SCORES_FILENAME: &str = "high_scores.ron";
+// Bump this and handle the old version in migrate() when changing HighScores' format.
+const HIGH_SCORES_VERSION: u32 = 1;
+const MAX_HIGH_SCORES: usize = 10;
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Score>()
+            .add_systems(Update, (
+                score_kills,
+                decay_combo,
+                record_high_score,
+            ).chain());
+        // Don't touch the player's high scores from tests.
+        if game::is_headless(app) {
+            app.init_resource::<HighScores>();
+        } else {
+            app
+                .insert_resource(load_high_scores())
+                .add_systems(Update, save_high_scores.run_if(on_event::<RunEndedEvent>()).after(record_high_score));
+        }
+    }
+}
+
+/// Points earned this run.
+#[derive(Clone, Debug, Deserialize, Serialize, Resource)]
+#[serde(default)]
+pub struct Score {
+    points: u64,
+    multiplier: f32,
+    best_multiplier: f32,
+    kills: u32,
+    // Seconds left before the multiplier starts to decay.
+    combo_timer: f32,
+}
+
+impl Default for Score {
+    fn default() -> Self {
+        Self {
+            points: 0,
+            multiplier: 1.0,
+            best_multiplier: 1.0,
+            kills: 0,
+            combo_timer: 0.0,
+        }
+    }
+}
+
+impl Score {
+    pub fn points(&self) -> u64 {
+        self.points
+    }
+
+    /// The combo multiplier applied to points from kills.
+    pub fn multiplier(&self) -> f32 {
+        self.multiplier
+    }
+
+    pub fn best_multiplier(&self) -> f32 {
+        self.best_multiplier
+    }
+
+    pub fn kills(&self) -> u32 {
+        self.kills
+    }
+
+    /// Adds points for a kill and builds up the combo.
+    pub fn add_kill(&mut self, value: u64) {
+        self.points += (value as f32 * self.multiplier).round() as u64;
+        self.kills += 1;
+        self.multiplier = (self.multiplier + COMBO_STEP).min(MAX_MULTIPLIER);
+        self.best_multiplier = self.best_multiplier.max(self.multiplier);
+        self.combo_timer = COMBO_TIMEOUT;
+    }
+
+    pub fn reset_combo(&mut self) {
+        self.multiplier = 1.0;
+        self.combo_timer = 0.0;
+    }
+}
+
+/// Points for killing an archetype, before the combo multiplier.
+pub fn kill_value(archetype: Archetype) -> u64 {
+    match archetype {
+        Archetype::BasicShooter => 0,
+        Archetype::Enemy => 100,
+        Archetype::Boss => 5000,
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HighScore {
+    pub points: u64,
+    pub wave: u32,
+    pub kills: u32,
+    /// How long the run lasted, in seconds.
+    pub duration: f32,
+    pub best_multiplier: f32,
+}
+
+/// The best runs, highest first.
+#[derive(Clone, Debug, Deserialize, Serialize, Resource)]
+#[serde(default)]
+pub struct HighScores {
+    pub version: u32,
+    pub entries: Vec<HighScore>,
+    /// Where the last finished run placed in the table, if it made it in.
+    #[serde(skip)]
+    pub last_rank: Option<usize>,
+}
+
+impl Default for HighScores {
+    fn default() -> Self {
+        Self {
+            version: HIGH_SCORES_VERSION,
+            entries: Vec::new(),
+            last_rank: None,
+        }
+    }
+}
+
+impl HighScores {
+    fn migrate(self) -> Result<Self, PersistError> {
+        if self.version > HIGH_SCORES_VERSION {
+            return Err(PersistError::UnsupportedVersion(self.version));
+        }
+        Ok(Self {
+            version: HIGH_SCORES_VERSION,
+            ..self
+        })
+    }
+
+    /// Adds a run to the table if it's good enough. Returns its rank.
+    pub fn insert(&mut self, high_score: HighScore) -> Option<usize> {
+        let rank = self.entries.iter()
+            .position(|entry| high_score.points > entry.points)
+            .unwrap_or(self.entries.len());
+        if rank >= MAX_HIGH_SCORES {
+            return None;
+        }
+        self.entries.insert(rank, high_score);
+        self.entries.truncate(MAX_HIGH_SCORES);
+        Some(rank)
+    }
+}
+
+fn load_high_scores() -> HighScores {
+    if cfg!(target_arch = "wasm32") {
+        return default();
+    }
+
+    let path = persist::config_path(HIGH_SCORES_FILENAME);
+    match persist::load_ron::<HighScores>(&path).and_then(|scores| scores.map(HighScores::migrate).transpose()) {
+        Ok(scores) => scores.unwrap_or_default(),
+        Err(e) => {
+            warn!("Could not load high scores from {}: {}", path.display(), e);
+            default()
+        },
+    }
+}
+
+fn score_kills(
+    run: Res<Run>,
+    mut score: ResMut<Score>,
+    mut death_events: EventReader<DeathEvent>,
+) {
+    for event in death_events.read() {
+        if run.is_over() {
+            continue;
+        }
+        match event.faction {
+            Faction::Enemy => score.add_kill(event.archetype.map(kill_value).unwrap_or_default()),
+            // Losing a shooter breaks the combo.
+            Faction::Player => score.reset_combo(),
+        }
+    }
+}
+
+fn decay_combo(
+    time: Res<Time>,
+    mut score: ResMut<Score>,
+) {
+    let dt = time.delta_seconds();
+    if score.combo_timer > 0.0 {
+        score.combo_timer -= dt;
+    } else if score.multiplier > 1.0 {
+        score.multiplier = (score.multiplier - COMBO_DECAY * dt).max(1.0);
+    }
+}
+
+fn record_high_score(
+    mut run_ended_events: EventReader<RunEndedEvent>,
+    run: Res<Run>,
+    score: Res<Score>,
+    waves: Res<WavesManager>,
+    mut high_scores: ResMut<HighScores>,
+) {
+    if run_ended_events.read().count() == 0 {
+        return;
+    }
+
+    high_scores.last_rank = high_scores.insert(HighScore {
+        points: score.points(),
+        wave: waves.wave(),
+        kills: score.kills(),
+        duration: run.elapsed(),
+        best_multiplier: score.best_multiplier(),
+    });
+}
+
+fn save_high_scores(
+    high_scores: Res<HighScores>,
+) {
+    if cfg!(target_arch = "wasm32") {
+        return;
+    }
+    let path = persist::config_path(HIGH_SCORES_FILENAME);
+    if let Err(e) = persist::save_ron(&path, &*high_scores) {
+        error!("Could not save high scores: {}", e);
+    }
+}
@@ -0,0 +1,337 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use bevy::utils::HashMap;
+use bevy_common_assets::ron::RonAssetPlugin;
+use benimator::FrameRate;
+use serde::Deserialize;
+
+use crate::game::combat::{DamageEvent, DeathEvent, FireEvent};
+
+const SPRITE_SHEETS_PATH: &str = "sprites/archetypes.sprites.ron";
+
+pub struct SpritesPlugin;
+
+impl Plugin for SpritesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(RonAssetPlugin::<SpriteSheets>::new(&["sprites.ron"]))
+            .register_type::<Archetype>()
+            .add_event::<PlayAnimation>()
+            .init_resource::<SpriteSheetsHandle>()
+            .init_resource::<LoadedSheets>()
+            .add_systems(Update, (
+                load_sprite_sheets,
+                attach_sprites,
+                trigger_animations,
+                animate_sprites,
+            ).chain());
+    }
+}
+
+/// The kind of thing an entity is, used to look up how to draw it. Archetypes without a sprite
+/// sheet are drawn with their vector shape.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Component, Reflect)]
+#[reflect(Component)]
+pub enum Archetype {
+    #[default]
+    BasicShooter,
+    Enemy,
+    Boss,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum AnimationKind {
+    /// Loops while nothing else is playing.
+    #[default]
+    Idle,
+    Fire,
+    Hit,
+    /// Played in place of the entity after it dies.
+    Death,
+}
+
+/// Send this to play a one-off animation on an entity. Does nothing if the entity is drawn with a
+/// vector shape or its sprite sheet doesn't have the animation.
+#[derive(Event)]
+pub struct PlayAnimation {
+    pub entity: Entity,
+    pub kind: AnimationKind,
+}
+
+#[derive(Deserialize, Asset, TypePath)]
+pub struct SpriteSheets {
+    sheets: HashMap<Archetype, SpriteSheetConfig>,
+}
+
+#[derive(Deserialize)]
+struct SpriteSheetConfig {
+    texture: String,
+    tile_size: Vec2,
+    columns: usize,
+    rows: usize,
+    #[serde(default = "default_scale")]
+    scale: f32,
+    animations: HashMap<AnimationKind, AnimationConfig>,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+struct AnimationConfig {
+    frames: Vec<usize>,
+    fps: f64,
+    /// Idle should repeat. Other animations go back to idle when they finish.
+    #[serde(default)]
+    repeat: bool,
+}
+
+impl AnimationConfig {
+    fn to_animation(&self) -> benimator::Animation {
+        let animation = benimator::Animation::from_indices(self.frames.iter().copied(), FrameRate::from_fps(self.fps));
+        if self.repeat {
+            animation.repeat()
+        } else {
+            animation.once()
+        }
+    }
+}
+
+#[derive(Resource)]
+struct SpriteSheetsHandle(Handle<SpriteSheets>);
+
+impl FromWorld for SpriteSheetsHandle {
+    fn from_world(world: &mut World) -> Self {
+        Self(world.resource::<AssetServer>().load(SPRITE_SHEETS_PATH))
+    }
+}
+
+struct LoadedSheet {
+    atlas: Handle<TextureAtlas>,
+    scale: f32,
+    animations: HashMap<AnimationKind, benimator::Animation>,
+}
+
+#[derive(Default, Resource)]
+struct LoadedSheets {
+    sheets: HashMap<Archetype, LoadedSheet>,
+}
+
+/// Added to entities drawn with a sprite instead of their vector shape.
+#[derive(Component)]
+pub struct SpriteVisual {
+    sprite: Entity,
+    // The vector shape's mesh, kept so it can be put back if the sprite sheet goes away.
+    shape_mesh: Option<Mesh2dHandle>,
+}
+
+impl SpriteVisual {
+    pub fn new(sprite: Entity, shape_mesh: Option<Mesh2dHandle>) -> Self {
+        Self {
+            sprite,
+            shape_mesh,
+        }
+    }
+
+    /// The child entity drawing the sprite.
+    pub fn sprite(&self) -> Entity {
+        self.sprite
+    }
+}
+
+#[derive(Component)]
+struct SpriteAnimator {
+    archetype: Archetype,
+    kind: AnimationKind,
+    state: benimator::State,
+}
+
+impl SpriteAnimator {
+    fn new(archetype: Archetype, kind: AnimationKind) -> Self {
+        Self {
+            archetype,
+            kind,
+            state: benimator::State::new(),
+        }
+    }
+
+    fn play(&mut self, kind: AnimationKind) {
+        self.kind = kind;
+        self.state.reset();
+    }
+}
+
+fn sprite_bundle(sheet: &LoadedSheet, transform: Transform) -> SpriteSheetBundle {
+    SpriteSheetBundle {
+        texture_atlas: sheet.atlas.clone(),
+        transform: transform.with_scale(Vec3::splat(sheet.scale)),
+        ..default()
+    }
+}
+
+/// The assets needed to build texture atlases from the sprite sheets config.
+#[derive(SystemParam)]
+struct SheetAssets<'w> {
+    asset_server: Res<'w, AssetServer>,
+    handle: Res<'w, SpriteSheetsHandle>,
+    sprite_sheets: Res<'w, Assets<SpriteSheets>>,
+    atlases: ResMut<'w, Assets<TextureAtlas>>,
+}
+
+fn load_sprite_sheets(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<SpriteSheets>>,
+    mut assets: SheetAssets,
+    mut loaded: ResMut<LoadedSheets>,
+    visual_q: Query<(Entity, &SpriteVisual)>,
+) {
+    let changed = asset_events.read().any(|event| match *event {
+        AssetEvent::Added { id } | AssetEvent::Modified { id } => id == assets.handle.0.id(),
+        _ => false,
+    });
+    if !changed {
+        return;
+    }
+    let SheetAssets { asset_server, handle, sprite_sheets, atlases } = &mut assets;
+    let Some(sprite_sheets) = sprite_sheets.get(&handle.0) else {
+        return;
+    };
+
+    loaded.sheets = sprite_sheets.sheets.iter()
+        .map(|(&archetype, config)| {
+            let atlas = TextureAtlas::from_grid(
+                asset_server.load(&config.texture),
+                config.tile_size,
+                config.columns,
+                config.rows,
+                None,
+                None,
+            );
+            let sheet = LoadedSheet {
+                atlas: atlases.add(atlas),
+                scale: config.scale,
+                animations: config.animations.iter()
+                    .map(|(&kind, animation)| (kind, animation.to_animation()))
+                    .collect(),
+            };
+            (archetype, sheet)
+        })
+        .collect();
+
+    // Go back to vector shapes so attach_sprites picks up the new sheets.
+    for (entity, visual) in visual_q.iter() {
+        commands.entity(visual.sprite).despawn_recursive();
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.remove::<SpriteVisual>();
+        if let Some(mesh) = visual.shape_mesh.clone() {
+            entity_commands.insert(mesh);
+        }
+    }
+}
+
+fn attach_sprites(
+    mut commands: Commands,
+    loaded: Res<LoadedSheets>,
+    archetype_q: Query<(Entity, &Archetype, Option<&Mesh2dHandle>), Without<SpriteVisual>>,
+) {
+    if loaded.sheets.is_empty() {
+        return;
+    }
+
+    for (entity, &archetype, mesh) in archetype_q.iter() {
+        let Some(sheet) = loaded.sheets.get(&archetype) else {
+            continue;
+        };
+
+        let sprite = commands.spawn((
+            Name::new("Sprite"),
+            sprite_bundle(sheet, Transform::default()),
+            SpriteAnimator::new(archetype, AnimationKind::Idle),
+        )).id();
+        commands.entity(entity)
+            .add_child(sprite)
+            .insert(SpriteVisual::new(sprite, mesh.cloned()))
+            .remove::<Mesh2dHandle>();
+    }
+}
+
+/// Events that start an animation.
+#[derive(SystemParam)]
+struct AnimationTriggers<'w, 's> {
+    play: EventReader<'w, 's, PlayAnimation>,
+    fire: EventReader<'w, 's, FireEvent>,
+    damage: EventReader<'w, 's, DamageEvent>,
+    death: EventReader<'w, 's, DeathEvent>,
+}
+
+fn trigger_animations(
+    mut commands: Commands,
+    loaded: Res<LoadedSheets>,
+    mut triggers: AnimationTriggers,
+    visual_q: Query<&SpriteVisual>,
+    mut animator_q: Query<&mut SpriteAnimator>,
+) {
+    let AnimationTriggers { play, fire, damage, death } = &mut triggers;
+    let requests = play.read()
+        .map(|event| (event.entity, event.kind))
+        .chain(fire.read().map(|event| (event.entity, AnimationKind::Fire)))
+        .chain(damage.read().map(|event| (event.entity, AnimationKind::Hit)));
+    for (entity, kind) in requests {
+        let Ok(visual) = visual_q.get(entity) else {
+            continue;
+        };
+        let Ok(mut animator) = animator_q.get_mut(visual.sprite) else {
+            continue;
+        };
+        let has_animation = loaded.sheets.get(&animator.archetype)
+            .is_some_and(|sheet| sheet.animations.contains_key(&kind));
+        if has_animation {
+            animator.play(kind);
+        }
+    }
+
+    // Dead entities are already gone, so play their death animation on a new sprite.
+    for event in death.read() {
+        let Some(archetype) = event.archetype else {
+            continue;
+        };
+        let Some(sheet) = loaded.sheets.get(&archetype) else {
+            continue;
+        };
+        if !sheet.animations.contains_key(&AnimationKind::Death) {
+            continue;
+        }
+        commands.spawn((
+            Name::new("DeathSprite"),
+            sprite_bundle(sheet, Transform::from_translation(event.pos.extend(0.0))),
+            SpriteAnimator::new(archetype, AnimationKind::Death),
+        ));
+    }
+}
+
+fn animate_sprites(
+    mut commands: Commands,
+    time: Res<Time>,
+    loaded: Res<LoadedSheets>,
+    mut animator_q: Query<(Entity, &mut SpriteAnimator, &mut TextureAtlasSprite)>,
+) {
+    for (entity, mut animator, mut sprite) in animator_q.iter_mut() {
+        let animator = &mut *animator;
+        let Some(animation) = loaded.sheets.get(&animator.archetype)
+            .and_then(|sheet| sheet.animations.get(&animator.kind)) else {
+            continue;
+        };
+
+        animator.state.update(animation, time.delta());
+        sprite.index = animator.state.frame_index();
+
+        if animator.state.is_ended() {
+            match animator.kind {
+                AnimationKind::Death => commands.entity(entity).despawn_recursive(),
+                _ => animator.play(AnimationKind::Idle),
+            }
+        }
+    }
+}
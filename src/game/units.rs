@@ -1,15 +1,22 @@
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     game::{
-        combat::HurtBoxBundle,
+        arena::Arena,
+        camera::CameraTarget,
+        combat::{FireEvent, HurtBoxBundle},
         factions::Faction,
+        feedback::DamageFeedback,
         health::Health,
         input::PlayerInput,
-        projectiles::ProjectileBundle,
+        particles::EngineTrail,
+        projectiles::Projectiles,
+        sprites::Archetype,
     },
     physics::{groups, PlayerMovement, Velocity},
+    settings::Settings,
 };
 
 pub struct UnitsPlugin;
@@ -18,6 +25,8 @@ impl Plugin for UnitsPlugin {
     fn build(&self, app: &mut App) {
         app
             .register_type::<SwarmParent>()
+            .register_type::<BasicShooter>()
+            .register_type::<UnitKind>()
             .add_systems(Update, (
                 shooter_flock_movement,
                 shooter_fire,
@@ -25,7 +34,8 @@ impl Plugin for UnitsPlugin {
     }
 }
 
-#[derive(Component, Reflect)]
+#[derive(Clone, Debug, Component, Reflect, Deserialize, Serialize)]
+#[reflect(Component)]
 pub struct SwarmParent {
     pub separation: f32,
     pub alignment: f32,
@@ -34,8 +44,20 @@ pub struct SwarmParent {
     pub cohesion_dist: f32,
     pub max_speed: f32,
     pub max_force: f32,
+    /// How strongly boids are pushed back inside the arena.
+    pub containment: f32,
+    /// How far from the arena's edge boids start getting pushed back.
+    pub containment_margin: f32,
     pub shooter_cooldown: f32,
+    // Game time isn't saved, so this would be meaningless after loading.
+    #[serde(skip)]
     pub last_fired_time: f32,
+    /// The kind of shooter spawned when the swarm grows.
+    pub unit_kind: UnitKind,
+    /// Added to the damage of every shot.
+    pub damage_bonus: f32,
+    /// Added to the max health of newly spawned shooters.
+    pub health_bonus: f32,
 }
 
 impl SwarmParent {
@@ -48,103 +70,204 @@ impl SwarmParent {
             cohesion_dist: 30.0,
             max_speed: 200.0,
             max_force: 1.0,
+            containment: 2.0,
+            containment_margin: 20.0,
             shooter_cooldown: 2.0,
             last_fired_time: -1.0,
+            unit_kind: UnitKind::Basic,
+            damage_bonus: 0.0,
+            health_bonus: 0.0,
         }
     }
 }
 
-#[derive(Component)]
+impl Default for SwarmParent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
 pub struct BasicShooter {
     last_fired: f32,
     cooldown: f32,
+    damage: f32,
+    kind: UnitKind,
+}
+
+impl BasicShooter {
+    pub fn kind(&self) -> UnitKind {
+        self.kind
+    }
+}
+
+/// The kinds of shooter a swarm can be made of. Everything but Basic has to be unlocked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize, Reflect)]
+pub enum UnitKind {
+    #[default]
+    Basic,
+    /// Tougher but hits no harder.
+    Tank,
+    /// Fragile but hits harder.
+    Gunner,
+}
+
+impl UnitKind {
+    pub const ALL: [Self; 3] = [Self::Basic, Self::Tank, Self::Gunner];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Basic => "Basic",
+            Self::Tank => "Tank",
+            Self::Gunner => "Gunner",
+        }
+    }
+
+    fn health(&self) -> f32 {
+        match self {
+            Self::Basic | Self::Gunner => 1.0,
+            Self::Tank => 3.0,
+        }
+    }
+
+    fn damage(&self) -> f32 {
+        match self {
+            Self::Basic | Self::Tank => 1.0,
+            Self::Gunner => 2.0,
+        }
+    }
+
+    fn sides(&self) -> usize {
+        match self {
+            Self::Basic | Self::Gunner => 3,
+            Self::Tank => 5,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            Self::Basic => Color::ORANGE * 4.0,
+            Self::Tank => Color::GOLD * 4.0,
+            Self::Gunner => Color::ORANGE_RED * 4.0,
+        }
+    }
+}
+
+/// The swarm's parent entity, without any shooters.
+#[derive(Bundle)]
+pub struct SwarmBundle {
+    name: Name,
+    swarm: SwarmParent,
+    movement: PlayerMovement,
+    input: PlayerInput,
+    spatial: SpatialBundle,
+    faction: Faction,
+    camera_target: CameraTarget,
+}
+
+impl SwarmBundle {
+    pub fn new(kind: UnitKind) -> Self {
+        Self {
+            name: Name::new("SwarmParent"),
+            swarm: SwarmParent {
+                unit_kind: kind,
+                ..SwarmParent::new()
+            },
+            movement: PlayerMovement::default(),
+            input: PlayerInput::default(),
+            spatial: SpatialBundle::default(),
+            faction: Faction::Player,
+            camera_target: CameraTarget,
+        }
+    }
 }
 
 #[derive(Bundle)]
 pub struct BasicShooterBundle {
     name: Name,
     shooter: BasicShooter,
+    archetype: Archetype,
     faction: Faction,
     velocity: Velocity,
+    trail: EngineTrail,
     health: Health,
+    feedback: DamageFeedback,
     hurt_box: HurtBoxBundle,
     shape: ShapeBundle,
     fill: Fill,
 }
 
 impl BasicShooterBundle {
-    pub fn new(pos: Vec2) -> Self {
+    pub fn new(pos: Vec2, kind: UnitKind) -> Self {
         let shape = shapes::RegularPolygon {
-            sides: 3,
+            sides: kind.sides(),
             feature: RegularPolygonFeature::Radius(20.0),
             ..default()
         };
         let transform = Transform::from_translation(pos.extend(0.0));
+        let color = kind.color();
         Self {
             name: Name::new("BasicShooter"),
             shooter: BasicShooter {
                 last_fired: -1.0,
                 cooldown: 1.0,
+                damage: kind.damage(),
+                kind,
             },
+            archetype: Archetype::BasicShooter,
             faction: Faction::Player,
             velocity: Velocity::default(),
-            health: Health::new(1.0),
+            trail: EngineTrail::new(0.08),
+            health: Health::new(kind.health()),
+            feedback: DamageFeedback::new(color),
             hurt_box: HurtBoxBundle::circle(16.0, groups::PLAYER),
             shape: ShapeBundle {
                 path: GeometryBuilder::build_as(&shape),
                 spatial: SpatialBundle::from_transform(transform),
                 ..default()
             },
-            fill: Fill::color(Color::ORANGE * 4.0),
+            fill: Fill::color(color),
         }
     }
+
+    /// Replaces the shooter's starting health, e.g. when loading a saved run.
+    pub fn with_health(mut self, health: Health) -> Self {
+        self.health = health;
+        self
+    }
 }
 
 pub fn spawn_swarm(
     commands: &mut Commands,
     swarm_q: &Query<Entity, With<SwarmParent>>,
     shooters: u32,
-) {
+    kind: UnitKind,
+) -> Entity {
     if let Ok(entity) = swarm_q.get_single() {
         commands.entity(entity).despawn_recursive();
     }
 
-    commands.spawn((
-        Name::new("SwarmParent"),
-        SwarmParent::new(),
-        PlayerMovement::default(),
-        PlayerInput::default(),
-        SpatialBundle::default(),
-        Faction::Player,
-    )).with_children(|b| {
+    commands.spawn(SwarmBundle::new(kind)).with_children(|b| {
         for _ in 0..shooters {
             let radius = 150.0;
             let x = (fastrand::f32() * 2.0) - 1.0;
             let y = (fastrand::f32() * 2.0) - 1.0;
             let pos = Vec2::new(x, y) * radius;
-            b.spawn(BasicShooterBundle::new(pos));
+            b.spawn(BasicShooterBundle::new(pos, kind));
         }
-    });
+    }).id()
 }
 
 pub fn resize_swarm(
     commands: &mut Commands,
-    swarm_q: &Query<(Entity, &Children), With<SwarmParent>>,
+    swarm_q: &Query<(Entity, &Children, &SwarmParent)>,
     shooters: u32,
 ) {
-    if let Ok((entity, children)) = swarm_q.get_single() {
+    if let Ok((entity, children, swarm)) = swarm_q.get_single() {
         if (shooters as usize) > children.len() {
             // Spawn more shooters.
-            let to_spawn = shooters as usize - children.len();
-            commands.entity(entity).with_children(|b| {
-                for _ in 0..to_spawn {
-                    let radius = 150.0;
-                    let x = (fastrand::f32() * 2.0) - 1.0;
-                    let y = (fastrand::f32() * 2.0) - 1.0;
-                    let pos = Vec2::new(x, y) * radius;
-                    b.spawn(BasicShooterBundle::new(pos));
-                }
-            });
+            add_shooters(commands, entity, swarm, shooters as usize - children.len());
         } else if (shooters as usize) < children.len() {
             // Despawn excess children!
             let to_despawn = children.len() - shooters as usize;
@@ -155,16 +278,38 @@ pub fn resize_swarm(
     }
 }
 
+/// Spawns shooters of the swarm's unit kind, with its health bonus.
+pub fn add_shooters(
+    commands: &mut Commands,
+    entity: Entity,
+    swarm: &SwarmParent,
+    count: usize,
+) {
+    commands.entity(entity).with_children(|b| {
+        for _ in 0..count {
+            let radius = 150.0;
+            let x = (fastrand::f32() * 2.0) - 1.0;
+            let y = (fastrand::f32() * 2.0) - 1.0;
+            let pos = Vec2::new(x, y) * radius;
+            let mut bundle = BasicShooterBundle::new(pos, swarm.unit_kind);
+            bundle.health.add_max(swarm.health_bonus);
+            b.spawn(bundle);
+        }
+    });
+}
+
 fn shooter_flock_movement(
+    arena: Res<Arena>,
     parent_q: Query<(&Children, &Transform, &SwarmParent)>,
     mut flock_q: Query<(&mut Transform, &mut Velocity), (With<BasicShooter>, Without<SwarmParent>)>,
 ) {
     // A simple boids simulation for the main cluster of ships.
 
     // TODO: Get list of children in parent. Do iter_many over those Entities.
-    let Ok((children, transform, swarm)) = parent_q.get_single() else {
+    let Ok((children, parent_transform, swarm)) = parent_q.get_single() else {
         return;
     };
+    let parent_pos = parent_transform.translation.truncate();
     // let swarm_pos = transform.translation.truncate();
     let swarm_pos = Vec2::ZERO;
 
@@ -232,9 +377,24 @@ fn shooter_flock_movement(
             }
         };
 
+        let containment = {
+            // Steer back toward the arena if we're getting close to leaving it.
+            let world_pos = parent_pos + pos;
+            let inside = arena.clamp(world_pos, swarm.containment_margin);
+            if inside != world_pos {
+                let desired = (inside - world_pos).clamp_length(swarm.max_speed, swarm.max_speed);
+                (desired - velocity.inner).clamp_length_max(swarm.max_force)
+            } else {
+                Vec2::ZERO
+            }
+        };
+
         // Update our physics.
         if let Ok((mut transform, mut velocity)) = flock_q.get_mut(*child) {
-            let accel = sepration * swarm.separation + alignment * swarm.alignment + cohesion * swarm.cohesion;
+            let accel = sepration * swarm.separation
+                + alignment * swarm.alignment
+                + cohesion * swarm.cohesion
+                + containment * swarm.containment;
             velocity.inner += accel;
             velocity.inner = velocity.inner.clamp_length_max(swarm.max_speed);
             transform.translation += velocity.inner.extend(0.0);
@@ -242,11 +402,33 @@ fn shooter_flock_movement(
     }
 }
 
+/// Turns dir toward the closest opponent inside a narrow cone in front of pos, if there is one.
+fn assisted_aim(
+    pos: Vec2,
+    dir: Vec2,
+    faction: Faction,
+    target_q: &Query<(&GlobalTransform, &Faction), With<Health>>,
+) -> Vec2 {
+    const MAX_ANGLE: f32 = 0.25;
+    const MAX_RANGE: f32 = 1200.0;
+
+    target_q.iter()
+        .filter(|(_, target_faction)| **target_faction != faction)
+        .map(|(transform, _)| transform.translation().truncate() - pos)
+        .filter(|offset| offset.length() < MAX_RANGE && dir.angle_between(*offset).abs() < MAX_ANGLE)
+        .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .map(|offset| offset.normalize())
+        .unwrap_or(dir)
+}
+
 fn shooter_fire(
-    mut commands: Commands,
+    mut projectiles: Projectiles,
     time: Res<Time>,
+    settings: Res<Settings>,
+    mut fire_events: EventWriter<FireEvent>,
     mut parent_q: Query<(&Children, &PlayerInput, &mut SwarmParent, &Faction)>,
     shooter_q: Query<(&GlobalTransform, &BasicShooter)>,
+    target_q: Query<(&GlobalTransform, &Faction), With<Health>>,
 ) {
     for (children, input, mut parent, faction) in parent_q.iter_mut() {
         if !input.shoot || children.is_empty() {
@@ -262,13 +444,157 @@ fn shooter_fire(
         // Pick a child at random to shoot from.
         // TODO: Make sure we pick a child that's a shooter.
         let entity = *fastrand::choice(children.iter()).unwrap();
-        let Ok((transform, _shooter)) = shooter_q.get(entity) else {
+        let Ok((transform, shooter)) = shooter_q.get(entity) else {
             continue;
         };
         let pos = transform.translation().truncate() + Vec2::Y * 20.0;
-        let vel = Vec2::Y * 1000.0;
-        commands.spawn(ProjectileBundle::new(pos, vel, 1.0, *faction));
+        let mut dir = Vec2::Y;
+        if settings.gameplay.aim_assist && *faction == Faction::Player {
+            dir = assisted_aim(pos, dir, *faction, &target_q);
+        }
+        projectiles.spawn(pos, dir * 1000.0, shooter.damage + parent.damage_bonus, *faction);
+        fire_events.send(FireEvent {
+            entity,
+            faction: *faction,
+            pos,
+            dir,
+        });
 
         parent.last_fired_time = now;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::game::{headless, projectiles::ProjectilesPlugin};
+
+    fn test_app() -> App {
+        let mut app = headless::minimal_app();
+        app
+            .add_event::<FireEvent>()
+            .init_resource::<Arena>()
+            .init_resource::<Settings>()
+            .add_plugins((
+                ProjectilesPlugin,
+                UnitsPlugin,
+            ));
+        app
+    }
+
+    fn spawn_test_swarm(app: &mut App, shooters: u32, kind: UnitKind) -> Entity {
+        let mut state = SystemState::<(Commands, Query<Entity, With<SwarmParent>>)>::new(&mut app.world);
+        let (mut commands, swarm_q) = state.get_mut(&mut app.world);
+        let entity = spawn_swarm(&mut commands, &swarm_q, shooters, kind);
+        state.apply(&mut app.world);
+        entity
+    }
+
+    fn resize(app: &mut App, shooters: u32) {
+        let mut state = SystemState::<(Commands, Query<(Entity, &Children, &SwarmParent)>)>::new(&mut app.world);
+        let (mut commands, swarm_q) = state.get_mut(&mut app.world);
+        resize_swarm(&mut commands, &swarm_q, shooters);
+        state.apply(&mut app.world);
+    }
+
+    fn shooters(app: &App, swarm: Entity) -> Vec<Entity> {
+        app.world.get::<Children>(swarm)
+            .map(|children| children.to_vec())
+            .unwrap_or_default()
+    }
+
+    fn shooter_count(app: &mut App) -> usize {
+        app.world.query::<&BasicShooter>().iter(&app.world).count()
+    }
+
+    #[test]
+    fn resize_grows_swarm_with_its_unit_kind() {
+        let mut app = test_app();
+        let swarm = spawn_test_swarm(&mut app, 10, UnitKind::Tank);
+        resize(&mut app, 25);
+
+        let children = shooters(&app, swarm);
+        assert_eq!(children.len(), 25);
+        for child in children {
+            assert_eq!(app.world.get::<BasicShooter>(child).unwrap().kind(), UnitKind::Tank);
+        }
+        assert_eq!(shooter_count(&mut app), 25);
+    }
+
+    #[test]
+    fn new_shooters_get_health_bonus() {
+        let mut app = test_app();
+        let swarm = spawn_test_swarm(&mut app, 10, UnitKind::Basic);
+        app.world.get_mut::<SwarmParent>(swarm).unwrap().health_bonus = 2.0;
+        resize(&mut app, 12);
+
+        let max_healths: Vec<f32> = shooters(&app, swarm).iter()
+            .map(|&child| app.world.get::<Health>(child).unwrap().max())
+            .collect();
+        assert_eq!(max_healths.iter().filter(|&&max| max == 1.0).count(), 10);
+        assert_eq!(max_healths.iter().filter(|&&max| max == 3.0).count(), 2);
+    }
+
+    #[test]
+    fn resize_shrinks_swarm() {
+        let mut app = test_app();
+        let swarm = spawn_test_swarm(&mut app, 20, UnitKind::Basic);
+        let before = shooters(&app, swarm);
+        resize(&mut app, 5);
+
+        let after = shooters(&app, swarm);
+        assert_eq!(after.len(), 5);
+        assert_eq!(shooter_count(&mut app), 5);
+        // The excess shooters are gone entirely, not just detached.
+        for entity in before.iter().filter(|entity| !after.contains(entity)) {
+            assert!(app.world.get_entity(*entity).is_none());
+        }
+    }
+
+    #[test]
+    fn resize_to_same_size_keeps_shooters() {
+        let mut app = test_app();
+        let swarm = spawn_test_swarm(&mut app, 8, UnitKind::Basic);
+        let before = shooters(&app, swarm);
+        resize(&mut app, 8);
+
+        assert_eq!(shooters(&app, swarm), before);
+    }
+
+    #[test]
+    fn respawning_swarm_replaces_old_one() {
+        let mut app = test_app();
+        let old = spawn_test_swarm(&mut app, 10, UnitKind::Basic);
+        let new = spawn_test_swarm(&mut app, 3, UnitKind::Gunner);
+
+        assert!(app.world.get_entity(old).is_none());
+        assert_eq!(shooters(&app, new).len(), 3);
+        assert_eq!(shooter_count(&mut app), 3);
+    }
+
+    #[test]
+    fn flock_stays_together_and_under_max_speed() {
+        fastrand::seed(7);
+        let mut app = test_app();
+        let swarm = spawn_test_swarm(&mut app, 30, UnitKind::Basic);
+        for _ in 0..600 {
+            app.update();
+        }
+
+        let max_speed = app.world.get::<SwarmParent>(swarm).unwrap().max_speed;
+        let children = shooters(&app, swarm);
+        assert_eq!(children.len(), 30);
+        let arena = app.world.resource::<Arena>();
+        for child in children {
+            let pos = app.world.get::<Transform>(child).unwrap().translation.truncate();
+            let velocity = app.world.get::<Velocity>(child).unwrap().inner;
+            assert!(pos.is_finite() && velocity.is_finite(), "shooter at {} moving {}", pos, velocity);
+            assert!(velocity.length() <= max_speed + 1e-3, "shooter moving {} is too fast", velocity);
+            // The swarm is at the origin, so local and world positions are the same.
+            assert!(arena.contains(pos), "shooter at {} left the arena", pos);
+            assert!(pos.length() < 500.0, "shooter at {} strayed from the swarm", pos);
+        }
+    }
+}
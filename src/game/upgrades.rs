@@ -0,0 +1,249 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_egui::{
+    egui::{self, Align2, RichText},
+    EguiContexts,
+};
+use serde::Deserialize;
+
+use crate::{
+    audio::SoundEvent,
+    game::{
+        self,
+        health::Health,
+        run::run_over,
+        units::{self, BasicShooter, SwarmParent},
+        waves::WavesManager,
+    },
+};
+
+const UPGRADE_POOL_PATH: &str = "upgrades/draft.upgrades.ron";
+// How many upgrades are offered each intermission.
+const OFFER_COUNT: usize = 3;
+
+pub struct UpgradesPlugin;
+
+impl Plugin for UpgradesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(RonAssetPlugin::<UpgradePool>::new(&["upgrades.ron"]))
+            .init_resource::<UpgradePoolHandle>()
+            .init_resource::<UpgradeDraft>();
+        if game::is_headless(app) {
+            app.add_systems(Update, (
+                roll_offers,
+                auto_pick_upgrade,
+            ).chain());
+        } else {
+            app.add_systems(Update, (
+                roll_offers,
+                upgrade_draft.run_if(not(run_over)),
+            ).chain());
+        }
+    }
+}
+
+/// Every upgrade that can be offered between waves.
+#[derive(Deserialize, Asset, TypePath)]
+pub struct UpgradePool {
+    offers: Vec<Upgrade>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Upgrade {
+    pub name: String,
+    pub description: String,
+    /// How likely this upgrade is to be offered compared to the others.
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    pub effects: Vec<UpgradeEffect>,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum UpgradeEffect {
+    /// Added to the swarm's separation weight.
+    Separation(f32),
+    /// Added to the swarm's alignment weight.
+    Alignment(f32),
+    /// Added to the swarm's cohesion weight.
+    Cohesion(f32),
+    /// Multiplies the swarm's max speed.
+    MaxSpeed(f32),
+    /// Multiplies the time between shots.
+    ShooterCooldown(f32),
+    /// Added to the damage of every shot.
+    Damage(f32),
+    /// Added to the max health of every shooter, including ones spawned later.
+    MaxHealth(f32),
+    AddUnits(u32),
+}
+
+#[derive(Resource)]
+struct UpgradePoolHandle(Handle<UpgradePool>);
+
+impl FromWorld for UpgradePoolHandle {
+    fn from_world(world: &mut World) -> Self {
+        Self(world.resource::<AssetServer>().load(UPGRADE_POOL_PATH))
+    }
+}
+
+/// The upgrades on offer in the current intermission.
+#[derive(Default, Resource)]
+pub struct UpgradeDraft {
+    pub offers: Vec<Upgrade>,
+}
+
+fn roll_offers(
+    pool_handle: Res<UpgradePoolHandle>,
+    pools: Res<Assets<UpgradePool>>,
+    mut draft: ResMut<UpgradeDraft>,
+    mut waves: ResMut<WavesManager>,
+) {
+    if !waves.in_intermission() {
+        draft.offers.clear();
+        return;
+    }
+    if !draft.offers.is_empty() {
+        return;
+    }
+
+    let Some(pool) = pools.get(&pool_handle.0) else {
+        return;
+    };
+    // Pick without repeats, favouring upgrades with a higher weight.
+    let mut candidates: Vec<&Upgrade> = pool.offers.iter()
+        .filter(|upgrade| upgrade.weight > 0.0)
+        .collect();
+    while draft.offers.len() < OFFER_COUNT && !candidates.is_empty() {
+        let total: f32 = candidates.iter().map(|upgrade| upgrade.weight).sum();
+        let mut roll = fastrand::f32() * total;
+        let i = candidates.iter()
+            .position(|upgrade| {
+                roll -= upgrade.weight;
+                roll <= 0.0
+            })
+            .unwrap_or(candidates.len() - 1);
+        draft.offers.push(candidates.swap_remove(i).clone());
+    }
+
+    // Nothing to pick from, so don't hold up the next wave.
+    if draft.offers.is_empty() {
+        warn!("Upgrade pool {} has no upgrades to offer", UPGRADE_POOL_PATH);
+        waves.end_intermission();
+    }
+}
+
+/// Applies one of the offered upgrades and ends the intermission.
+#[derive(SystemParam)]
+struct UpgradePicker<'w, 's> {
+    commands: Commands<'w, 's>,
+    draft: ResMut<'w, UpgradeDraft>,
+    waves: ResMut<'w, WavesManager>,
+    swarm_q: Query<'w, 's, (Entity, &'static mut SwarmParent, Option<&'static Children>)>,
+    health_q: Query<'w, 's, &'static mut Health, With<BasicShooter>>,
+}
+
+impl<'w, 's> UpgradePicker<'w, 's> {
+    fn offers(&self) -> &[Upgrade] {
+        &self.draft.offers
+    }
+
+    fn pick(&mut self, i: usize) {
+        let upgrade = self.draft.offers.swap_remove(i);
+        if let Ok((swarm, mut parent, children)) = self.swarm_q.get_single_mut() {
+            apply_upgrade(&mut self.commands, &upgrade, swarm, &mut parent, children, &mut self.health_q);
+        }
+        self.draft.offers.clear();
+        self.waves.end_intermission();
+    }
+}
+
+fn apply_upgrade(
+    commands: &mut Commands,
+    upgrade: &Upgrade,
+    swarm: Entity,
+    parent: &mut SwarmParent,
+    children: Option<&Children>,
+    health_q: &mut Query<&mut Health, With<BasicShooter>>,
+) {
+    for effect in upgrade.effects.iter() {
+        match *effect {
+            UpgradeEffect::Separation(amount) => parent.separation += amount,
+            UpgradeEffect::Alignment(amount) => parent.alignment += amount,
+            UpgradeEffect::Cohesion(amount) => parent.cohesion += amount,
+            UpgradeEffect::MaxSpeed(factor) => parent.max_speed *= factor,
+            UpgradeEffect::ShooterCooldown(factor) => parent.shooter_cooldown *= factor,
+            UpgradeEffect::Damage(amount) => parent.damage_bonus += amount,
+            UpgradeEffect::MaxHealth(amount) => {
+                parent.health_bonus += amount;
+                let Some(children) = children else {
+                    continue;
+                };
+                for &child in children.iter() {
+                    if let Ok(mut health) = health_q.get_mut(child) {
+                        health.add_max(amount);
+                    }
+                }
+            },
+            UpgradeEffect::AddUnits(count) => {
+                units::add_shooters(commands, swarm, parent, count as usize);
+            },
+        }
+    }
+    // Negative weights would turn the flocking inside out.
+    parent.separation = parent.separation.max(0.0);
+    parent.alignment = parent.alignment.max(0.0);
+    parent.cohesion = parent.cohesion.max(0.0);
+}
+
+fn upgrade_draft(
+    mut egui_ctx: EguiContexts,
+    mut picker: UpgradePicker,
+    mut sound_events: EventWriter<SoundEvent>,
+) {
+    if picker.offers().is_empty() {
+        return;
+    }
+
+    let ctx = egui_ctx.ctx_mut();
+    let mut picked = None;
+
+    egui::Window::new("Choose an Upgrade")
+        .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (i, upgrade) in picker.offers().iter().enumerate() {
+                    ui.vertical(|ui| {
+                        ui.label(RichText::new(&upgrade.name).strong());
+                        ui.label(&upgrade.description);
+                        if ui.button("Pick").clicked() {
+                            picked = Some(i);
+                        }
+                    });
+                }
+            });
+        });
+
+    if let Some(i) = picked {
+        picker.pick(i);
+        sound_events.send(SoundEvent::Pickup);
+    }
+}
+
+/// Stands in for the player when running headless, so waves keep coming.
+fn auto_pick_upgrade(
+    mut picker: UpgradePicker,
+) {
+    if picker.offers().is_empty() {
+        return;
+    }
+    let i = fastrand::usize(..picker.offers().len());
+    picker.pick(i);
+}
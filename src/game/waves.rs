@@ -2,48 +2,268 @@ use bevy::prelude::*;
 
 use crate::game::{
     ai,
-    enemies::EnemyBundle,
+    enemies::{Boss, EnemyBundle},
+    patterns::{EmitterAim, PatternEmitterBundle},
 };
 
+// Seconds of quiet between clearing a wave and the next one arriving.
+const WAVE_BREAK: f32 = 3.0;
+// Every this many waves is a boss wave.
+const BOSS_WAVE_INTERVAL: u32 = 5;
+pub const BOSS_PATTERN: &str = "patterns/spiral.pattern.ron";
+
 pub struct WavesPlugin;
 
 impl Plugin for WavesPlugin {
     fn build(&self, app: &mut App) {
         app
+            .register_type::<WaveEntity>()
             .init_resource::<WavesManager>()
             .add_systems(Update, waves_manager);
     }
 }
 
-#[derive(Default, Resource)]
+#[derive(Resource)]
 pub struct WavesManager {
     spawned_enemies: Vec<Entity>,
+    wave: u32,
+    wave_size: usize,
+    break_timer: Timer,
+    // Waiting for the player to pick an upgrade before the break can run out.
+    intermission: bool,
+}
+
+impl Default for WavesManager {
+    fn default() -> Self {
+        Self {
+            spawned_enemies: Vec::new(),
+            wave: 0,
+            wave_size: 0,
+            // The first wave arrives right away.
+            break_timer: Timer::from_seconds(0.0, TimerMode::Once),
+            intermission: false,
+        }
+    }
 }
 
-#[derive(Component)]
+impl WavesManager {
+    /// The current wave, starting at 1. 0 before the first wave arrives.
+    pub fn wave(&self) -> u32 {
+        self.wave
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.spawned_enemies.len()
+    }
+
+    /// How much of the current wave has been cleared, from 0.0 to 1.0.
+    pub fn progress(&self) -> f32 {
+        if self.wave_size == 0 {
+            return 1.0;
+        }
+        1.0 - self.remaining() as f32 / self.wave_size as f32
+    }
+
+    /// Whether the last wave is cleared and the next one hasn't arrived yet.
+    pub fn in_break(&self) -> bool {
+        self.spawned_enemies.is_empty()
+    }
+
+    pub fn is_boss_wave(&self) -> bool {
+        self.wave > 0 && self.wave % BOSS_WAVE_INTERVAL == 0
+    }
+
+    /// Whether the next wave is held back until an upgrade is picked.
+    pub fn in_intermission(&self) -> bool {
+        self.intermission
+    }
+
+    /// Waves that were fully cleared. A wave in progress doesn't count.
+    pub fn completed_waves(&self) -> u32 {
+        if self.in_break() {
+            self.wave
+        } else {
+            self.wave.saturating_sub(1)
+        }
+    }
+
+    /// Picks up a saved run after its last completed wave. The next wave arrives after a break.
+    pub fn resume(completed_waves: u32, intermission: bool) -> Self {
+        Self {
+            wave: completed_waves,
+            break_timer: Timer::from_seconds(WAVE_BREAK, TimerMode::Once),
+            intermission,
+            ..default()
+        }
+    }
+
+    /// Makes these entities the current wave, e.g. after loading a scenario.
+    pub fn replace_wave(&mut self, enemies: Vec<Entity>) {
+        self.wave = self.wave.max(1);
+        self.wave_size = enemies.len();
+        self.spawned_enemies = enemies;
+        self.intermission = false;
+    }
+
+    /// Lets the break run out and the next wave arrive.
+    pub fn end_intermission(&mut self) {
+        self.intermission = false;
+    }
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
 pub struct WaveEntity;
 
 fn waves_manager(
     mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
     mut waves_manager: ResMut<WavesManager>,
     mut removed: RemovedComponents<WaveEntity>
 ) {
     for entity in removed.read() {
         if let Some(i) = waves_manager.spawned_enemies.iter().position(|&e| e == entity) {
             waves_manager.spawned_enemies.swap_remove(i);
+            if waves_manager.spawned_enemies.is_empty() {
+                waves_manager.break_timer = Timer::from_seconds(WAVE_BREAK, TimerMode::Once);
+                waves_manager.intermission = true;
+            }
+        }
+    }
+
+    if !waves_manager.in_break() || waves_manager.in_intermission() {
+        return;
+    }
+    if !waves_manager.break_timer.tick(time.delta()).finished() {
+        return;
+    }
+
+    spawn_wave(&mut commands, &asset_server, &mut waves_manager);
+}
+
+fn spawn_wave(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    waves_manager: &mut WavesManager,
+) {
+    let x = -500.0;
+    let spacing = 100.0;
+    for i in 0..10 {
+        let entity = commands.spawn((
+            EnemyBundle::new(Vec2::new(x + spacing * i as f32, 500.0)),
+            ai::SimpleShooterAi::new(3.0, 0.0..=2.0),
+            WaveEntity,
+        )).id();
+        waves_manager.spawned_enemies.push(entity);
+    }
+    waves_manager.wave += 1;
+    if waves_manager.is_boss_wave() {
+        let pattern = asset_server.load(BOSS_PATTERN);
+        let entity = commands.spawn((
+            EnemyBundle::boss(Vec2::new(0.0, 650.0)),
+            PatternEmitterBundle::from_asset(pattern, EmitterAim::NearestOpponent),
+            Boss,
+            WaveEntity,
+        )).id();
+        waves_manager.spawned_enemies.push(entity);
+    }
+    waves_manager.wave_size = waves_manager.spawned_enemies.len();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::headless;
+
+    fn test_app() -> App {
+        let mut app = headless::minimal_app();
+        app.add_plugins(WavesPlugin);
+        // The first wave arrives on the first update.
+        app.update();
+        app
+    }
+
+    fn wave_entities(app: &mut App) -> Vec<Entity> {
+        app.world.query_filtered::<Entity, With<WaveEntity>>()
+            .iter(&app.world)
+            .collect()
+    }
+
+    #[test]
+    fn first_wave_arrives_right_away() {
+        let mut app = test_app();
+        let waves = app.world.resource::<WavesManager>();
+        assert_eq!(waves.wave(), 1);
+        assert_eq!(waves.remaining(), 10);
+        assert_eq!(waves.completed_waves(), 0);
+        assert_eq!(wave_entities(&mut app).len(), 10);
+    }
+
+    #[test]
+    fn despawned_enemies_are_no_longer_tracked() {
+        let mut app = test_app();
+        for entity in wave_entities(&mut app).into_iter().take(4) {
+            app.world.despawn(entity);
         }
+        app.update();
+
+        let waves = app.world.resource::<WavesManager>();
+        assert_eq!(waves.remaining(), 6);
+        assert!((waves.progress() - 0.4).abs() < 1e-6);
+        assert!(!waves.in_break());
     }
 
-    if waves_manager.spawned_enemies.is_empty() {
-        let x = -500.0;
-        let spacing = 100.0;
-        for i in 0..10 {
-            let entity = commands.spawn((
-                EnemyBundle::new(Vec2::new(x + spacing * i as f32, 500.0)),
-                ai::SimpleShooterAi::new(3.0, 0.0..=2.0),
-                WaveEntity,
-            )).id();
-            waves_manager.spawned_enemies.push(entity);
+    #[test]
+    fn removing_wave_entity_component_counts_as_cleared() {
+        let mut app = test_app();
+        let entity = wave_entities(&mut app)[0];
+        app.world.entity_mut(entity).remove::<WaveEntity>();
+        app.update();
+
+        assert_eq!(app.world.resource::<WavesManager>().remaining(), 9);
+    }
+
+    #[test]
+    fn untracked_wave_entities_are_ignored() {
+        let mut app = test_app();
+        let stray = app.world.spawn(WaveEntity).id();
+        app.update();
+        app.world.despawn(stray);
+        app.update();
+
+        assert_eq!(app.world.resource::<WavesManager>().remaining(), 10);
+    }
+
+    #[test]
+    fn next_wave_waits_for_intermission_then_break() {
+        let mut app = test_app();
+        for entity in wave_entities(&mut app) {
+            app.world.despawn(entity);
+        }
+        app.update();
+
+        let waves = app.world.resource::<WavesManager>();
+        assert!(waves.in_break());
+        assert!(waves.in_intermission());
+        assert_eq!(waves.completed_waves(), 1);
+
+        // Nothing arrives until an upgrade is picked, however long it takes.
+        let break_ticks = (WAVE_BREAK / headless::TICK.as_secs_f32()).ceil() as u32;
+        for _ in 0..break_ticks * 2 {
+            app.update();
+        }
+        assert_eq!(app.world.resource::<WavesManager>().wave(), 1);
+
+        app.world.resource_mut::<WavesManager>().end_intermission();
+        // Allow an extra tick or two for rounding in the tick length.
+        for _ in 0..break_ticks + 2 {
+            app.update();
         }
+        let waves = app.world.resource::<WavesManager>();
+        assert_eq!(waves.wave(), 2);
+        assert_eq!(waves.remaining(), 10);
+        assert!(!waves.in_intermission());
+        assert_eq!(wave_entities(&mut app).len(), 10);
     }
 }
@@ -2,21 +2,26 @@
 
 use bevy::prelude::*;
 use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
-use bevy::window::{Cursor, WindowMode};
+use bevy::window::Cursor;
 use bevy_kira_audio::AudioPlugin;
 use bevy_prototype_lyon::plugin::ShapePlugin;
 use bevy_rapier2d::prelude::*;
 
+mod audio;
 mod debug;
 mod game;
 mod log;
+mod persist;
 mod physics;
+mod settings;
 mod window;
 
-// TODO: Choose a good size for this game.
-// const GAME_SIZE: (f32, f32) = (320.0, 180.0);
+/// The logical resolution of the game. The window is always an integer multiple of this.
+const GAME_SIZE: (f32, f32) = (480.0, 270.0);
 const DEFAULT_SCALE: u8 = 3;
 const ALLOW_EXIT: bool = cfg!(not(target_arch = "wasm32"));
+// Ticks to simulate when running with --headless and no tick count.
+const DEFAULT_HEADLESS_TICKS: u32 = 60 * 60;
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, States)]
 enum AppState {
@@ -30,8 +35,18 @@ fn main() {
     #[cfg(target_arch = "wasm32")]
     console_error_panic_hook::set_once();
 
+    // Play without a window, e.g. `--headless 600`, to check gameplay on machines without a GPU.
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("--headless") {
+        let ticks = args.next()
+            .and_then(|ticks| ticks.parse().ok())
+            .unwrap_or(DEFAULT_HEADLESS_TICKS);
+        game::headless::simulate(ticks);
+        return;
+    }
+
     // TODO: Try to initialize logging before this. Maybe we can also make this code run in a plugin.
-    let saved_window_state = window::load_window_state();
+    let (saved_window_state, window_state_error) = window::load_window_state();
     let cursor = Cursor {
         visible: true,
         ..default()
@@ -42,16 +57,7 @@ fn main() {
         .set(log::log_plugin())
         .set(ImagePlugin::default_nearest())
         .set(WindowPlugin {
-            primary_window: Some(Window {
-                title: window::WINDOW_TITLE.into(),
-                // width: GAME_SIZE.0 * saved_window_state.scale as f32,
-                // height: GAME_SIZE.1 * saved_window_state.scale as f32,
-                resizable: false,
-                position: saved_window_state.position,
-                mode: WindowMode::Windowed,
-                cursor,
-                ..default()
-            }),
+            primary_window: Some(saved_window_state.primary_window(cursor)),
             ..default()
         });
 
@@ -80,10 +86,12 @@ fn main() {
         // App setup
         .add_state::<AppState>()
         .add_plugins((
-            window::WindowPlugin::new(saved_window_state),
+            window::WindowPlugin::new(saved_window_state, window_state_error),
+            audio::AudioPlugin,
             debug::DebugPlugin,
-            game::GamePlugin,
+            game::GamePlugin::default(),
             physics::PhysicsPlugin,
+            settings::SettingsPlugin,
         ));
 
     if ALLOW_EXIT {
@@ -0,0 +1,107 @@
+use std::{env, fmt, fs, io};
+use std::path::{Path, PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Set this to store config and save files somewhere other than the default per-user directory.
+pub const CONFIG_DIR_ENV_VAR: &str = "BEVY_JAM_4_CONFIG_DIR";
+const APP_DIR_NAME: &str = "bevy-jam-4";
+
+#[derive(Debug)]
+pub enum PersistError {
+    Io(io::Error),
+    Deserialize(ron::error::SpannedError),
+    Serialize(ron::Error),
+    /// The file was written by a newer version of the game.
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::Deserialize(e) => write!(f, "could not parse file: {}", e),
+            Self::Serialize(e) => write!(f, "could not serialize: {}", e),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported version {}", version),
+        }
+    }
+}
+
+impl std::error::Error for PersistError {}
+
+impl From<io::Error> for PersistError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for PersistError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        Self::Deserialize(e)
+    }
+}
+
+impl From<ron::Error> for PersistError {
+    fn from(e: ron::Error) -> Self {
+        Self::Serialize(e)
+    }
+}
+
+/// The per-user directory where config and save files live.
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = env::var_os(CONFIG_DIR_ENV_VAR) {
+        return dir.into();
+    }
+
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| Path::new(&home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+
+    // Fall back to the working directory if we can't find anything better.
+    base.map(|base| base.join(APP_DIR_NAME))
+        .unwrap_or_default()
+}
+
+pub fn config_path(filename: &str) -> PathBuf {
+    config_dir().join(filename)
+}
+
+/// Loads a RON file. Returns Ok(None) if the file doesn't exist.
+pub fn load_ron<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, PersistError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some(ron::from_str(&contents)?))
+}
+
+/// Deletes a file. Does nothing if it doesn't exist.
+pub fn remove(path: &Path) -> Result<(), PersistError> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Saves a RON file by writing to a temporary file and moving it into place, so a crash mid-write
+/// can't leave a corrupted file behind.
+pub fn save_ron<T: Serialize>(path: &Path, value: &T) -> Result<(), PersistError> {
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
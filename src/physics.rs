@@ -25,11 +25,14 @@ pub struct PhysicsPlugin;
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
         app
+            .register_type::<Velocity>()
+            .register_type::<PlayerMovement>()
             .add_systems(Update, update_movement);
     }
 }
 
-#[derive(Clone, Copy, Default, Component)]
+#[derive(Clone, Copy, Default, Component, Reflect)]
+#[reflect(Component)]
 pub struct Velocity {
     pub inner: Vec2,
 }
@@ -42,25 +45,47 @@ impl Velocity {
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 pub struct PlayerMovement {
     speed: f32,
+    /// How close to a wall this can get.
+    wall_margin: f32,
 }
 
 impl Default for PlayerMovement {
     fn default() -> Self {
         Self {
             speed: 600.0,
+            wall_margin: 100.0,
         }
     }
 }
 
 fn update_movement(
     time: Res<Time>,
+    rapier_context: Res<RapierContext>,
     mut q: Query<(&PlayerInput, &PlayerMovement, &mut Transform)>,
 ) {
+    let filter = QueryFilter::new()
+        .groups(CollisionGroups::new(groups::ALL, groups::WORLD));
     for (input, movement, mut transform) in q.iter_mut() {
-        transform.translation += input.movement.extend(0.0) * time.delta_seconds() * movement.speed;
+        let delta = input.movement * time.delta_seconds() * movement.speed;
+
+        // Move along each axis separately so we can slide along walls.
+        for axis_delta in [Vec2::new(delta.x, 0.0), Vec2::new(0.0, delta.y)] {
+            let dist = axis_delta.length();
+            if dist == 0.0 {
+                continue;
+            }
+            let dir = axis_delta / dist;
+            let pos = transform.translation.truncate();
+            let allowed = match rapier_context.cast_ray(pos, dir, dist + movement.wall_margin, true, filter) {
+                Some((_, toi)) => (toi - movement.wall_margin).clamp(0.0, dist),
+                None => dist,
+            };
+            transform.translation += (dir * allowed).extend(0.0);
+        }
     }
 }
 
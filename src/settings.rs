@@ -0,0 +1,444 @@
+use bevy::prelude::*;
+use bevy::app::AppExit;
+use bevy_egui::{
+    egui::{self, ComboBox, DragValue, Grid, Slider},
+    EguiContexts,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::SoundEvent,
+    persist::{self, PersistError},
+    window::{DisplayMode, WindowState, MAX_SCALE},
+};
+
+const SETTINGS_FILENAME: &str = "settings.ron";
+// Bump this and handle the old version in migrate() when changing Settings' format.
+const SETTINGS_VERSION: u32 = 1;
+// The furthest the zoom limits can be set in the menu.
+const ZOOM_RANGE: (f32, f32) = (0.5, 32.0);
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(load_settings())
+            .init_resource::<SettingsMenu>()
+            .add_systems(Update, (
+                toggle_settings_menu,
+                capture_binding,
+                settings_menu.run_if(settings_menu_open),
+                save_settings_on_close,
+            ).chain());
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            app.add_systems(Last, save_settings.run_if(on_event::<AppExit>()));
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, Resource)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub video: VideoSettings,
+    pub audio: AudioSettings,
+    pub input: InputBindings,
+    pub gameplay: GameplaySettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            video: default(),
+            audio: default(),
+            input: default(),
+            gameplay: default(),
+        }
+    }
+}
+
+impl Settings {
+    fn migrate(self) -> Result<Self, PersistError> {
+        if self.version > SETTINGS_VERSION {
+            return Err(PersistError::UnsupportedVersion(self.version));
+        }
+        Ok(Self {
+            version: SETTINGS_VERSION,
+            ..self
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VideoSettings {
+    pub bloom: bool,
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        Self {
+            bloom: true,
+        }
+    }
+}
+
+/// Volumes from 0.0 to 1.0. Channel volumes are scaled by the master volume.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub ui: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 0.8,
+            music: 0.7,
+            sfx: 0.8,
+            ui: 0.8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InputBindings {
+    pub move_up: KeyCode,
+    pub move_down: KeyCode,
+    pub move_left: KeyCode,
+    pub move_right: KeyCode,
+    pub shoot: KeyCode,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self {
+            move_up: KeyCode::W,
+            move_down: KeyCode::S,
+            move_left: KeyCode::A,
+            move_right: KeyCode::D,
+            shoot: KeyCode::Space,
+        }
+    }
+}
+
+impl InputBindings {
+    pub fn get(&self, action: InputAction) -> KeyCode {
+        match action {
+            InputAction::MoveUp => self.move_up,
+            InputAction::MoveDown => self.move_down,
+            InputAction::MoveLeft => self.move_left,
+            InputAction::MoveRight => self.move_right,
+            InputAction::Shoot => self.shoot,
+        }
+    }
+
+    fn get_mut(&mut self, action: InputAction) -> &mut KeyCode {
+        match action {
+            InputAction::MoveUp => &mut self.move_up,
+            InputAction::MoveDown => &mut self.move_down,
+            InputAction::MoveLeft => &mut self.move_left,
+            InputAction::MoveRight => &mut self.move_right,
+            InputAction::Shoot => &mut self.shoot,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Shoot,
+}
+
+impl InputAction {
+    const ALL: [Self; 5] = [Self::MoveUp, Self::MoveDown, Self::MoveLeft, Self::MoveRight, Self::Shoot];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::MoveUp => "Move Up",
+            Self::MoveDown => "Move Down",
+            Self::MoveLeft => "Move Left",
+            Self::MoveRight => "Move Right",
+            Self::Shoot => "Shoot",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    /// Scales how much the camera shakes, from 0.0 to 1.0.
+    pub screen_shake: f32,
+    /// Nudges shots toward nearby enemies.
+    pub aim_assist: bool,
+    /// How far the camera can zoom in. Lower is closer.
+    pub min_zoom: f32,
+    /// How far the camera can zoom out.
+    pub max_zoom: f32,
+}
+
+impl Default for GameplaySettings {
+    fn default() -> Self {
+        Self {
+            screen_shake: 1.0,
+            aim_assist: false,
+            min_zoom: 1.5,
+            max_zoom: 16.0,
+        }
+    }
+}
+
+fn load_settings() -> Settings {
+    if cfg!(target_arch = "wasm32") {
+        return default();
+    }
+
+    let path = persist::config_path(SETTINGS_FILENAME);
+    match persist::load_ron::<Settings>(&path).and_then(|settings| settings.map(Settings::migrate).transpose()) {
+        Ok(settings) => settings.unwrap_or_default(),
+        Err(e) => {
+            warn!("Could not load settings from {}, using defaults: {}", path.display(), e);
+            default()
+        },
+    }
+}
+
+fn save_settings(
+    settings: Res<Settings>,
+) {
+    let path = persist::config_path(SETTINGS_FILENAME);
+    info!("Saving settings to {}", path.display());
+
+    if let Err(e) = persist::save_ron(&path, &*settings) {
+        error!("Could not save settings: {}", e);
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum SettingsTab {
+    #[default]
+    Video,
+    Audio,
+    Controls,
+    Gameplay,
+}
+
+#[derive(Default, Resource)]
+pub struct SettingsMenu {
+    pub open: bool,
+    tab: SettingsTab,
+    // The action waiting for a key press to bind to it.
+    rebinding: Option<InputAction>,
+}
+
+pub fn settings_menu_open(
+    settings_menu: Res<SettingsMenu>,
+) -> bool {
+    settings_menu.open
+}
+
+fn toggle_settings_menu(
+    keys: Res<Input<KeyCode>>,
+    mut egui_ctx: EguiContexts,
+    mut settings_menu: ResMut<SettingsMenu>,
+    mut sound_events: EventWriter<SoundEvent>,
+) {
+    if egui_ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::F1) {
+        settings_menu.open = !settings_menu.open;
+        settings_menu.rebinding = None;
+        sound_events.send(SoundEvent::UiClick);
+    }
+}
+
+fn capture_binding(
+    keys: Res<Input<KeyCode>>,
+    mut settings_menu: ResMut<SettingsMenu>,
+    mut settings: ResMut<Settings>,
+) {
+    let Some(action) = settings_menu.rebinding else {
+        return;
+    };
+    if let Some(&key) = keys.get_just_pressed().next() {
+        *settings.input.get_mut(action) = key;
+        settings_menu.rebinding = None;
+    }
+}
+
+fn save_settings_on_close(
+    settings_menu: Res<SettingsMenu>,
+    settings: Res<Settings>,
+    mut was_open: Local<bool>,
+) {
+    if *was_open && !settings_menu.open && !cfg!(target_arch = "wasm32") {
+        save_settings(settings);
+    }
+    *was_open = settings_menu.open;
+}
+
+fn settings_menu(
+    mut egui_ctx: EguiContexts,
+    mut settings_menu: ResMut<SettingsMenu>,
+    mut settings: ResMut<Settings>,
+    mut window_state: ResMut<WindowState>,
+) {
+    let ctx = egui_ctx.ctx_mut();
+    let settings_menu = &mut *settings_menu;
+
+    // Edit copies so resources are only marked as changed when something actually changes.
+    let mut new_settings = settings.clone();
+    let mut new_window_state = window_state.clone();
+
+    egui::Window::new("Settings")
+        .open(&mut settings_menu.open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut settings_menu.tab, SettingsTab::Video, "Video");
+                ui.selectable_value(&mut settings_menu.tab, SettingsTab::Audio, "Audio");
+                ui.selectable_value(&mut settings_menu.tab, SettingsTab::Controls, "Controls");
+                ui.selectable_value(&mut settings_menu.tab, SettingsTab::Gameplay, "Gameplay");
+            });
+            ui.separator();
+
+            match settings_menu.tab {
+                SettingsTab::Video => video_settings_ui(ui, &mut new_settings.video, &mut new_window_state),
+                SettingsTab::Audio => audio_settings_ui(ui, &mut new_settings.audio),
+                SettingsTab::Controls => controls_settings_ui(ui, &new_settings.input, &mut settings_menu.rebinding),
+                SettingsTab::Gameplay => gameplay_settings_ui(ui, &mut new_settings.gameplay),
+            }
+
+            ui.separator();
+            if ui.button("Reset to Defaults").clicked() {
+                new_settings = default();
+                settings_menu.rebinding = None;
+            }
+        });
+
+    settings.set_if_neq(new_settings);
+    window_state.set_if_neq(new_window_state);
+}
+
+fn video_settings_ui(ui: &mut egui::Ui, video: &mut VideoSettings, window_state: &mut WindowState) {
+    Grid::new("video_settings")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Display Mode");
+            ComboBox::from_id_source("display_mode")
+                .selected_text(format!("{:?}", window_state.mode))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut window_state.mode, DisplayMode::Windowed, "Windowed");
+                    ui.selectable_value(&mut window_state.mode, DisplayMode::BorderlessFullscreen, "Borderless Fullscreen");
+                    ui.selectable_value(&mut window_state.mode, DisplayMode::Fullscreen, "Fullscreen");
+                });
+            ui.end_row();
+
+            ui.label("Scale");
+            let scale_response = ui.add(Slider::new(&mut window_state.scale, 1..=MAX_SCALE));
+            if scale_response.changed() {
+                // Picking a scale overrides any size from resizing the window.
+                window_state.size = None;
+            }
+            ui.end_row();
+
+            ui.label("Monitor");
+            ui.horizontal(|ui| {
+                let mut pick_monitor = window_state.monitor.is_some();
+                ui.checkbox(&mut pick_monitor, "");
+                let mut index = window_state.monitor.unwrap_or_default();
+                ui.add_enabled(pick_monitor, DragValue::new(&mut index).clamp_range(0..=8));
+                let monitor = pick_monitor.then_some(index);
+                if monitor != window_state.monitor {
+                    window_state.set_monitor(monitor);
+                }
+            });
+            ui.end_row();
+
+            ui.label("VSync");
+            ui.checkbox(&mut window_state.vsync, "");
+            ui.end_row();
+
+            ui.label("Resizable");
+            ui.checkbox(&mut window_state.resizable, "");
+            ui.end_row();
+
+            ui.label("Bloom");
+            ui.checkbox(&mut video.bloom, "");
+            ui.end_row();
+        });
+}
+
+fn audio_settings_ui(ui: &mut egui::Ui, audio: &mut AudioSettings) {
+    Grid::new("audio_settings")
+        .num_columns(2)
+        .show(ui, |ui| {
+            for (label, volume) in [
+                ("Master", &mut audio.master),
+                ("Music", &mut audio.music),
+                ("Sound Effects", &mut audio.sfx),
+                ("Interface", &mut audio.ui),
+            ] {
+                ui.label(label);
+                ui.add(Slider::new(volume, 0.0..=1.0).show_value(false));
+                ui.end_row();
+            }
+        });
+}
+
+fn controls_settings_ui(ui: &mut egui::Ui, input: &InputBindings, rebinding: &mut Option<InputAction>) {
+    Grid::new("controls_settings")
+        .num_columns(2)
+        .show(ui, |ui| {
+            for action in InputAction::ALL {
+                ui.label(action.label());
+                let text = if *rebinding == Some(action) {
+                    "Press a key...".to_string()
+                } else {
+                    format!("{:?}", input.get(action))
+                };
+                if ui.button(text).clicked() {
+                    *rebinding = Some(action);
+                }
+                ui.end_row();
+            }
+        });
+}
+
+fn gameplay_settings_ui(ui: &mut egui::Ui, gameplay: &mut GameplaySettings) {
+    Grid::new("gameplay_settings")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Screen Shake");
+            ui.add(Slider::new(&mut gameplay.screen_shake, 0.0..=1.0).show_value(false));
+            ui.end_row();
+
+            ui.label("Aim Assist");
+            ui.checkbox(&mut gameplay.aim_assist, "");
+            ui.end_row();
+
+            // Each limit can't cross the other so the camera always has room to zoom.
+            let max_zoom = gameplay.max_zoom;
+            ui.label("Min Zoom");
+            ui.add(Slider::new(&mut gameplay.min_zoom, ZOOM_RANGE.0..=max_zoom).logarithmic(true));
+            ui.end_row();
+
+            let min_zoom = gameplay.min_zoom;
+            ui.label("Max Zoom");
+            ui.add(Slider::new(&mut gameplay.max_zoom, min_zoom..=ZOOM_RANGE.1).logarithmic(true));
+            ui.end_row();
+        });
+}
@@ -1,43 +1,176 @@
-use std::fs;
 use std::path::Path;
 
 use bevy::prelude::*;
 use bevy::app::AppExit;
+use bevy::core_pipeline::clear_color::ClearColorConfig;
 use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
-use bevy::window::PrimaryWindow;
+use bevy::render::camera::Viewport;
+use bevy::render::view::RenderLayers;
+use bevy::window::{Cursor, MonitorSelection, PresentMode, PrimaryWindow, WindowMode};
+use bevy_egui::EguiContexts;
 use serde::{Deserialize, Serialize};
 
-use crate::DEFAULT_SCALE;
+use crate::{
+    persist::{self, PersistError},
+    DEFAULT_SCALE,
+    GAME_SIZE,
+};
 
 pub const WINDOW_TITLE: &str = "Bevy Jam 4";
 const WINDOW_STATE_FILENAME: &str = "window_state.ron";
+// Bump this and handle the old version in migrate() when changing WindowState's format.
+const WINDOW_STATE_VERSION: u32 = 2;
+pub const MAX_SCALE: u8 = 8;
+// Nothing is ever put on this layer so the letterbox camera only clears the screen.
+const LETTERBOX_LAYER: u8 = RenderLayers::TOTAL_LAYERS as u8 - 1;
 
-#[derive(Clone, Debug, Deserialize, Serialize, Resource)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DisplayMode {
+    #[default]
+    Windowed,
+    BorderlessFullscreen,
+    Fullscreen,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, Resource)]
 pub struct WindowState {
+    /// Files saved before versioning was added are version 0.
     #[serde(default)]
-    pub position: WindowPosition,
+    pub version: u32,
     #[serde(default)]
+    pub position: WindowPosition,
+    #[serde(default = "default_scale")]
     pub scale: u8,
+    #[serde(default)]
+    pub mode: DisplayMode,
+    /// Which monitor to open on. None means let the OS decide.
+    #[serde(default)]
+    pub monitor: Option<usize>,
+    /// The size of a resizable window. When None, the size comes from scale.
+    #[serde(default)]
+    pub size: Option<Vec2>,
+    #[serde(default = "default_vsync")]
+    pub vsync: bool,
+    #[serde(default)]
+    pub resizable: bool,
+}
+
+fn default_scale() -> u8 {
+    DEFAULT_SCALE
+}
+
+fn default_vsync() -> bool {
+    true
+}
+
+/// The window size for the given integer scale of the game's resolution.
+pub fn scaled_resolution(scale: u8) -> (f32, f32) {
+    let scale = scale.clamp(1, MAX_SCALE) as f32;
+    (GAME_SIZE.0 * scale, GAME_SIZE.1 * scale)
 }
 
 impl Default for WindowState {
     fn default() -> Self {
         Self {
+            version: WINDOW_STATE_VERSION,
             position: WindowPosition::Automatic,
             scale: DEFAULT_SCALE,
+            mode: DisplayMode::Windowed,
+            monitor: None,
+            size: None,
+            vsync: true,
+            resizable: false,
         }
     }
 }
 
-pub fn load_window_state() -> WindowState {
-    if Path::new(WINDOW_STATE_FILENAME).is_file() {
-        // TODO: Log errors if these fail and return default.
-        let window_state_str = fs::read_to_string(WINDOW_STATE_FILENAME)
-            .expect("Could not read window state file");
-        ron::from_str(&window_state_str)
-            .expect("Could not deserialize window state")
-    } else {
-        default()
+impl WindowState {
+    pub fn window_mode(&self) -> WindowMode {
+        match self.mode {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::BorderlessFullscreen => WindowMode::BorderlessFullscreen,
+            DisplayMode::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+
+    /// The size of the window when it's not fullscreen.
+    pub fn window_size(&self) -> (f32, f32) {
+        match self.size {
+            Some(size) if self.resizable => (size.x, size.y),
+            _ => scaled_resolution(self.scale),
+        }
+    }
+
+    fn window_position(&self) -> WindowPosition {
+        match (self.position, self.monitor) {
+            (WindowPosition::Automatic, Some(index)) => WindowPosition::Centered(MonitorSelection::Index(index)),
+            (position, _) => position,
+        }
+    }
+
+    /// Creates the primary window from the saved state.
+    pub fn primary_window(&self, cursor: Cursor) -> Window {
+        Window {
+            title: WINDOW_TITLE.into(),
+            resolution: self.window_size().into(),
+            resizable: self.resizable,
+            position: self.window_position(),
+            mode: self.window_mode(),
+            present_mode: self.present_mode(),
+            cursor,
+            ..default()
+        }
+    }
+
+    /// Moves the window to the center of the given monitor.
+    pub fn set_monitor(&mut self, monitor: Option<usize>) {
+        self.monitor = monitor;
+        self.position = match monitor {
+            Some(index) => WindowPosition::Centered(MonitorSelection::Index(index)),
+            None => WindowPosition::Automatic,
+        };
+    }
+
+    fn migrate(mut self) -> Result<Self, PersistError> {
+        if self.version > WINDOW_STATE_VERSION {
+            return Err(PersistError::UnsupportedVersion(self.version));
+        }
+        if self.version == 0 {
+            // Version 0 could save a scale of 0.
+            self.scale = self.scale.clamp(1, MAX_SCALE);
+        }
+        // Version 1 didn't have any of the display settings. Serde fills in their defaults.
+        self.version = WINDOW_STATE_VERSION;
+        Ok(self)
+    }
+}
+
+/// Loads the saved window state, falling back to the default if it can't be loaded. Logging isn't
+/// set up yet when this is called, so any error is returned to be logged later.
+pub fn load_window_state() -> (WindowState, Option<PersistError>) {
+    if cfg!(target_arch = "wasm32") {
+        // There's no file system to load from.
+        return (default(), None);
+    }
+
+    let path = persist::config_path(WINDOW_STATE_FILENAME);
+    // Older versions saved to the working directory.
+    let loaded = match persist::load_ron::<WindowState>(&path) {
+        Ok(None) => persist::load_ron(Path::new(WINDOW_STATE_FILENAME)),
+        loaded => loaded,
+    };
+
+    match loaded.and_then(|state| state.map(WindowState::migrate).transpose()) {
+        Ok(state) => (state.unwrap_or_default(), None),
+        Err(e) => (default(), Some(e)),
     }
 }
 
@@ -52,21 +185,34 @@ impl Default for LogFpsTimer {
 
 pub struct WindowPlugin {
     saved_window_state: WindowState,
+    load_error: Option<String>,
 }
 
 impl WindowPlugin {
-    pub fn new(saved_window_state: WindowState) -> Self {
+    pub fn new(saved_window_state: WindowState, load_error: Option<PersistError>) -> Self {
         Self {
             saved_window_state,
+            load_error: load_error.map(|e| e.to_string()),
         }
     }
 }
 
 impl Plugin for WindowPlugin {
     fn build(&self, app: &mut App) {
+        if let Some(e) = &self.load_error {
+            let e = e.clone();
+            app.add_systems(Startup, move || warn!("Could not load window state, using defaults: {}", e));
+        }
+
         app
             .insert_resource(self.saved_window_state.clone())
-            .add_systems(PostUpdate, update_window_state);
+            .add_systems(Startup, spawn_letterbox_camera)
+            .add_systems(Update, change_scale)
+            .add_systems(PostUpdate, (
+                apply_window_state.run_if(resource_changed::<WindowState>()),
+                update_window_state,
+                update_camera_viewports,
+            ).chain());
         #[cfg(not(target_arch = "wasm32"))]
         {
             app.add_systems(Last, save_window_state_on_exit.run_if(on_event::<AppExit>()));
@@ -83,7 +229,128 @@ fn update_window_state(
     window_q: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
 ) {
     if let Ok(window) = window_q.get_single() {
-        window_state.position = window.position;
+        if window_state.position != window.position {
+            window_state.position = window.position;
+        }
+        if window_state.resizable && window.mode == WindowMode::Windowed {
+            let size = Vec2::new(window.resolution.width(), window.resolution.height());
+            if window_state.size != Some(size) {
+                window_state.size = Some(size);
+            }
+        }
+    }
+}
+
+/// Applies changes to the window state, e.g. from the settings menu, to the window.
+fn apply_window_state(
+    window_state: Res<WindowState>,
+    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = window_q.get_single_mut() else {
+        return;
+    };
+
+    let mode = window_state.window_mode();
+    if window.mode != mode {
+        window.mode = mode;
+    }
+    let present_mode = window_state.present_mode();
+    if window.present_mode != present_mode {
+        window.present_mode = present_mode;
+    }
+    if window.resizable != window_state.resizable {
+        window.resizable = window_state.resizable;
+    }
+    if window.position != window_state.position {
+        window.position = window_state.position;
+    }
+    if mode == WindowMode::Windowed {
+        let (width, height) = window_state.window_size();
+        if window.resolution.width() != width || window.resolution.height() != height {
+            window.resolution.set(width, height);
+        }
+    }
+}
+
+fn change_scale(
+    keys: Res<Input<KeyCode>>,
+    mut egui_ctx: EguiContexts,
+    mut window_state: ResMut<WindowState>,
+) {
+    if egui_ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    let scale = if keys.any_just_pressed([KeyCode::Equals, KeyCode::NumpadAdd]) {
+        window_state.scale.saturating_add(1)
+    } else if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        window_state.scale.saturating_sub(1)
+    } else {
+        return;
+    };
+    let scale = scale.clamp(1, MAX_SCALE);
+    if scale == window_state.scale {
+        return;
+    }
+
+    window_state.scale = scale;
+    window_state.size = None;
+}
+
+/// Clears the parts of the window outside the game's viewport.
+#[derive(Component)]
+struct LetterboxCamera;
+
+fn spawn_letterbox_camera(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        Name::new("LetterboxCamera"),
+        Camera2dBundle {
+            camera: Camera {
+                order: -1,
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::Custom(Color::BLACK),
+            },
+            ..default()
+        },
+        RenderLayers::layer(LETTERBOX_LAYER),
+        LetterboxCamera,
+    ));
+}
+
+/// Renders cameras at the biggest integer multiple of the game's resolution that fits in the
+/// window, centered.
+fn update_camera_viewports(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<&mut Camera, Without<LetterboxCamera>>,
+) {
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+    let window_size = UVec2::new(window.physical_width(), window.physical_height());
+    if window_size.x == 0 || window_size.y == 0 {
+        // Minimized.
+        return;
+    }
+
+    let game_size = UVec2::new(GAME_SIZE.0 as u32, GAME_SIZE.1 as u32);
+    let scale = (window_size / game_size).min_element().max(1);
+    let size = (game_size * scale).min(window_size);
+    let pos = (window_size - size) / 2;
+
+    for mut camera in camera_q.iter_mut() {
+        let up_to_date = camera.viewport.as_ref()
+            .is_some_and(|viewport| viewport.physical_position == pos && viewport.physical_size == size);
+        if !up_to_date {
+            camera.viewport = Some(Viewport {
+                physical_position: pos,
+                physical_size: size,
+                ..default()
+            });
+        }
     }
 }
 
@@ -111,11 +378,10 @@ fn log_fps_in_window_title(
 fn save_window_state_on_exit(
     window_state: Res<WindowState>,
 ) {
-    info!("Saving window state");
+    let path = persist::config_path(WINDOW_STATE_FILENAME);
+    info!("Saving window state to {}", path.display());
 
-    let pretty_config = ron::ser::PrettyConfig::default();
-    let state_str = ron::ser::to_string_pretty(&*window_state, pretty_config)
-        .expect("Could not serialize window state");
-    fs::write(WINDOW_STATE_FILENAME, state_str)
-        .expect("Could not write window state to file");
+    if let Err(e) = persist::save_ron(&path, &*window_state) {
+        error!("Could not save window state: {}", e);
+    }
 }